@@ -1,89 +1,568 @@
-pub(crate) fn run(input: String) -> eyre::Result<()> {
+use std::collections::BTreeMap;
+use std::f64::consts::TAU;
+use std::fmt;
+use std::str::FromStr;
+
+use clap::{Parser, ValueEnum};
+
+use crate::util::{Coord, Grid};
+
+#[derive(Parser)]
+struct Options {
+    /// The number of positions on the dial
+    #[arg(long, default_value_t = DEFAULT_DIAL_SIZE)]
+    dial_size: usize,
+    /// The position the dial starts at
+    #[arg(long, default_value_t = 50)]
+    start: usize,
+    /// A position to count landings and passes for, can be repeated
+    #[arg(long = "watch", default_values_t = [0])]
+    watched: Vec<usize>,
+    /// Print what every instruction did to the dial
+    #[arg(long, value_enum)]
+    trace: Option<TraceFormat>,
+    /// For multi-dial instructions, make a full turn of each dial move the next one
+    #[arg(long)]
+    geared: bool,
+    /// Find the start position that gives the most landings or passes
+    #[arg(long, value_enum)]
+    best_start: Option<Goal>,
+    /// Find the shortest instruction sequence from the start to this position
+    #[arg(long)]
+    reach: Option<usize>,
+    /// How many times the dial must point at zero on the way to --reach
+    #[arg(long, default_value_t = 0, requires = "reach")]
+    passes: usize,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum TraceFormat {
+    Csv,
+    Json,
+    Dial,
+}
+
+pub(crate) fn run(input: String, args: &[String]) -> eyre::Result<()> {
+    let options: Options = crate::parse_day_options("01", args);
+
+    // multi-dial instructions name their dial, e.g. `A:L68`
+    if input.contains(':') {
+        return run_lock(&input, options);
+    }
+
     let instructions = parse_input(input);
-    let mut safe = Safe::new(50);
-    let mut zero_counts = 0;
-    for instruction in &instructions {
-        safe.rotate(instruction.0, instruction.1);
-        if safe.position == 0 {
-            zero_counts += 1;
+
+    if let Some(goal) = options.best_start {
+        let (start, score) =
+            Safe::best_start(options.dial_size, &options.watched, &instructions, goal)?;
+        let goal = format!("{goal:?}").to_lowercase();
+        println!("Best start: {start} ({score} {goal})");
+    }
+
+    if let Some(target) = options.reach {
+        let safe = Safe::with_dial(options.dial_size, options.start, [0])?;
+        match safe.shortest_sequence(target, options.passes) {
+            Some(sequence) => {
+                let sequence = sequence.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+                println!("Sequence: [{}]", sequence.join(", "));
+            }
+            None => println!(
+                "Position {target} can't be reached passing zero exactly {} times",
+                options.passes
+            ),
         }
     }
-    println!("Part 1: {zero_counts}");
 
-    let mut safe = Safe::new(50);
-    let mut zero_counts = 0;
+    let mut safe = Safe::with_dial(options.dial_size, options.start, options.watched)?;
+    let trace = safe.trace(&instructions);
+    match options.trace {
+        Some(TraceFormat::Csv) => print!("{}", trace_csv(&trace)),
+        Some(TraceFormat::Json) => println!("{}", trace_json(&trace)),
+        Some(TraceFormat::Dial) => {
+            for step in &trace {
+                println!(
+                    "{}: {} -> {}, {} passes{}",
+                    step.instruction,
+                    step.start,
+                    step.end,
+                    step.passes,
+                    if step.landed { ", landed" } else { "" }
+                );
+                print!("{}", render_dial(&safe, step));
+            }
+        }
+        None => {}
+    }
+
+    let tallies = safe.tallies();
+    if tallies.len() > 1 {
+        print_tallies("", tallies);
+    }
+
+    let landings = tallies.values().map(|t| t.landings).sum::<usize>();
+    println!("Part 1: {landings}");
+
+    let passes = tallies.values().map(|t| t.passes).sum::<usize>();
+    println!("Part 2: {passes}");
+
+    Ok(())
+}
+
+fn run_lock(input: &str, options: Options) -> eyre::Result<()> {
+    if options.trace.is_some() {
+        eyre::bail!("--trace is only supported for single dial instructions");
+    }
+
+    let instructions = parse_lock_input(input)?;
+    let dial_count = instructions.iter().map(|i| i.dial + 1).max().unwrap_or(1);
+    let mut lock = Lock::new(
+        dial_count,
+        options.dial_size,
+        options.start,
+        options.watched,
+        options.geared,
+    )?;
     for instruction in &instructions {
-        zero_counts += safe.rotate(instruction.0, instruction.1);
+        lock.rotate(*instruction);
+    }
+
+    for (i, dial) in lock.dials.iter().enumerate() {
+        println!("Dial {} finished at {}", dial_label(i), dial.position);
+        print_tallies("  ", dial.tallies());
     }
-    println!("Part 2: {zero_counts}");
+
+    let tallies = lock.dials.iter().flat_map(|d| d.tallies().values());
+    let landings = tallies.clone().map(|t| t.landings).sum::<usize>();
+    println!("Part 1: {landings}");
+
+    let passes = tallies.map(|t| t.passes).sum::<usize>();
+    println!("Part 2: {passes}");
 
     Ok(())
 }
 
+fn print_tallies(indent: &str, tallies: &BTreeMap<usize, Tally>) {
+    for (position, tally) in tallies {
+        println!(
+            "{indent}Position {position}: {} landings, {} passes",
+            tally.landings, tally.passes
+        );
+    }
+}
+
 fn parse_input(input: String) -> Vec<Instruction> {
     input
         .lines()
-        .map(|line| {
-            let (direction, clicks) = line.split_at(1);
-            let direction = match direction {
-                "L" => Direction::Left,
-                "R" => Direction::Right,
-                _ => unreachable!(),
-            };
-            Instruction(direction, clicks.parse().unwrap())
-        })
+        .map(|line| line.parse().unwrap())
         .collect()
 }
 
+fn parse_lock_input(input: &str) -> eyre::Result<Vec<LockInstruction>> {
+    input.lines().map(|line| line.parse()).collect()
+}
+
+/// The number of positions on the puzzle's dial
+const DEFAULT_DIAL_SIZE: usize = 100;
+
+/// How often the dial has pointed at a watched position
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Tally {
+    /// Rotations that finished on the position
+    landings: usize,
+    /// Clicks that left the dial pointing at the position, landings included
+    passes: usize,
+}
+
 struct Safe {
-    position: isize,
+    size: usize,
+    position: usize,
+    watched: BTreeMap<usize, Tally>,
 }
 
 impl Safe {
-    fn new(position: isize) -> Self {
-        Self { position }
+    fn with_dial(
+        size: usize,
+        position: usize,
+        watched: impl IntoIterator<Item = usize>,
+    ) -> eyre::Result<Self> {
+        if size == 0 {
+            eyre::bail!("The dial needs at least one position");
+        }
+
+        let watched = watched
+            .into_iter()
+            .map(|p| {
+                if p < size {
+                    Ok((p, Tally::default()))
+                } else {
+                    Err(eyre::eyre!("Watched position {p} is not on a dial of size {size}"))
+                }
+            })
+            .collect::<eyre::Result<BTreeMap<_, _>>>()?;
+
+        if position >= size {
+            eyre::bail!("Start position {position} is not on a dial of size {size}");
+        }
+
+        Ok(Self {
+            size,
+            position,
+            watched,
+        })
+    }
+
+    fn tallies(&self) -> &BTreeMap<usize, Tally> {
+        &self.watched
+    }
+
+    /// Run every instruction, recording what each one did
+    fn trace(&mut self, instructions: &[Instruction]) -> Vec<TraceStep> {
+        instructions
+            .iter()
+            .map(|&instruction| {
+                let start = self.position;
+                let passes = self.rotate(instruction.0, instruction.1);
+                TraceStep {
+                    instruction,
+                    start,
+                    end: self.position,
+                    passes,
+                    landed: self.watched.contains_key(&self.position),
+                }
+            })
+            .collect()
     }
 
+    /// Rotate the dial, returning how many clicks left it pointing at a watched position
+    ///
+    /// Computed arithmetically, so the cost doesn't depend on the number of clicks.
     fn rotate(&mut self, direction: Direction, clicks: usize) -> usize {
-        let mut times_hit_zero = 0;
+        let start = self.position;
+        let remainder = clicks % self.size;
+        self.position = match direction {
+            Direction::Right => (start + remainder) % self.size,
+            Direction::Left => (start + self.size - remainder) % self.size,
+        };
 
-        for _ in 0..clicks {
-            match direction {
-                Direction::Left => self.position -= 1,
-                Direction::Right => self.position += 1,
+        let mut total = 0;
+        for (&target, tally) in self.watched.iter_mut() {
+            let passes = times_pointing_at(self.size, start, target, direction, clicks);
+            tally.passes += passes;
+            if self.position == target {
+                tally.landings += 1;
             }
+            total += passes;
+        }
+
+        total
+    }
+}
 
-            if self.position < 0 {
-                self.position += 100;
-            } else if self.position >= 100 {
-                self.position -= 100;
+/// How many of `clicks` clicks from `start` leave a dial of `size` pointing at `target`
+fn times_pointing_at(
+    size: usize,
+    start: usize,
+    target: usize,
+    direction: Direction,
+    clicks: usize,
+) -> usize {
+    let distance = match direction {
+        Direction::Right => (target + size - start) % size,
+        Direction::Left => (start + size - target) % size,
+    };
+
+    // a target we're already on is next reached after a full turn
+    let first_hit = if distance == 0 { size } else { distance };
+    if clicks >= first_hit {
+        (clicks - first_hit) / size + 1
+    } else {
+        0
+    }
+}
+
+/// What the solver should maximize
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Goal {
+    Landings,
+    Passes,
+}
+
+impl Safe {
+    /// Find the start position scoring highest for `goal` over `instructions`
+    ///
+    /// Returns the position and its score, preferring the lowest position on ties.
+    fn best_start(
+        size: usize,
+        watched: &[usize],
+        instructions: &[Instruction],
+        goal: Goal,
+    ) -> eyre::Result<(usize, usize)> {
+        let mut best = None;
+        for start in 0..size {
+            let mut safe = Safe::with_dial(size, start, watched.iter().copied())?;
+            for instruction in instructions {
+                safe.rotate(instruction.0, instruction.1);
             }
 
-            if self.position == 0 {
-                times_hit_zero += 1;
+            let score = safe
+                .tallies()
+                .values()
+                .map(|t| match goal {
+                    Goal::Landings => t.landings,
+                    Goal::Passes => t.passes,
+                })
+                .sum::<usize>();
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((start, score));
             }
         }
 
-        times_hit_zero
+        best.ok_or(eyre::eyre!("The dial needs at least one position"))
+    }
+
+    /// Find the fewest instructions that take the dial to `target`, pointing at zero
+    /// exactly `passes` times along the way
+    ///
+    /// Ties are broken by the fewest clicks, then by turning right. Returns `None`
+    /// when no sequence exists, e.g. finishing on zero without passing it.
+    fn shortest_sequence(&self, target: usize, passes: usize) -> Option<Vec<Instruction>> {
+        if target >= self.size {
+            return None;
+        }
+        if target == self.position && passes == 0 {
+            return Some(Vec::new());
+        }
+
+        // Passes only ever add up, and one of the two ways round to the target
+        // avoids zero unless the target is zero itself. So a single instruction
+        // does as well as any longer sequence; each extra full turn adds a pass.
+        [Direction::Right, Direction::Left]
+            .into_iter()
+            .filter_map(|direction| {
+                let distance = match direction {
+                    Direction::Right => (target + self.size - self.position) % self.size,
+                    Direction::Left => (self.position + self.size - target) % self.size,
+                };
+                let clicks = if distance == 0 { self.size } else { distance };
+                let minimum = times_pointing_at(self.size, self.position, 0, direction, clicks);
+                let extra_turns = passes.checked_sub(minimum)?;
+                let extra_clicks = extra_turns.checked_mul(self.size)?;
+                Some(Instruction(direction, clicks.checked_add(extra_clicks)?))
+            })
+            .min_by_key(|instruction| instruction.1)
+            .map(|instruction| vec![instruction])
     }
 }
 
+/// A combination lock made of several identical dials
+///
+/// When geared, the dials work like an odometer: every time a dial turns
+/// past zero it carries one click in the same direction onto the next dial.
+struct Lock {
+    dials: Vec<Safe>,
+    geared: bool,
+}
+
+impl Lock {
+    fn new(
+        dial_count: usize,
+        size: usize,
+        position: usize,
+        watched: Vec<usize>,
+        geared: bool,
+    ) -> eyre::Result<Self> {
+        let dials = (0..dial_count)
+            .map(|_| Safe::with_dial(size, position, watched.iter().copied()))
+            .collect::<eyre::Result<Vec<_>>>()?;
+        Ok(Self { dials, geared })
+    }
+
+    /// Rotate one dial, returning how many clicks of any dial pointed at a watched position
+    ///
+    /// A carry out of the last dial is dropped.
+    fn rotate(&mut self, instruction: LockInstruction) -> usize {
+        let LockInstruction { dial, instruction } = instruction;
+        let Instruction(direction, clicks) = instruction;
+        let Some(safe) = self.dials.get_mut(dial) else {
+            return 0;
+        };
+
+        let start = safe.position;
+        let passes = safe.rotate(direction, clicks);
+        if !self.geared {
+            return passes;
+        }
+
+        // turning right carries as the dial reaches zero, turning left as it leaves it
+        let wrap_position = match direction {
+            Direction::Right => 0,
+            Direction::Left => safe.size - 1,
+        };
+        let carry = times_pointing_at(safe.size, start, wrap_position, direction, clicks);
+        if carry == 0 {
+            return passes;
+        }
+
+        let next = LockInstruction {
+            dial: dial + 1,
+            instruction: Instruction(direction, carry),
+        };
+        passes + self.rotate(next)
+    }
+}
+
+/// What a single instruction did to the dial
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TraceStep {
+    instruction: Instruction,
+    start: usize,
+    end: usize,
+    /// Clicks that pointed at a watched position (zero by default)
+    passes: usize,
+    /// Whether the rotation finished on a watched position
+    landed: bool,
+}
+
+fn trace_csv(trace: &[TraceStep]) -> String {
+    let mut csv = String::from("instruction,start,end,passes,landed\n");
+    for step in trace {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            step.instruction, step.start, step.end, step.passes, step.landed
+        ));
+    }
+    csv
+}
+
+fn trace_json(trace: &[TraceStep]) -> String {
+    let steps = trace
+        .iter()
+        .map(|step| {
+            format!(
+                "{{\"instruction\":\"{}\",\"start\":{},\"end\":{},\"passes\":{},\"landed\":{}}}",
+                step.instruction, step.start, step.end, step.passes, step.landed
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", steps.join(","))
+}
+
+/// The number of rows from the center of a rendered dial to its edge
+const DIAL_RADIUS: f64 = 6.0;
+
+/// The most points drawn around the ring, enough to reach every cell it passes through
+const RING_POINTS: usize = 128;
+
+/// Draw the dial as a ring, with position 0 at the top and positions increasing clockwise
+///
+/// Watched positions are marked `o`, the start `S` and the end `E` (`*` if they're the same).
+fn render_dial(safe: &Safe, step: &TraceStep) -> String {
+    let height = 2 * DIAL_RADIUS as usize + 1;
+    let width = 4 * DIAL_RADIUS as usize + 1;
+    let mut grid = Grid::new(width, height);
+    grid.set((DIAL_RADIUS as usize, 2 * DIAL_RADIUS as usize), "+");
+
+    let cell_at = |fraction: f64| {
+        let angle = TAU * fraction;
+        // columns are about half as wide as rows are tall
+        let row = DIAL_RADIUS - DIAL_RADIUS * angle.cos();
+        let col = 2.0 * DIAL_RADIUS + 2.0 * DIAL_RADIUS * angle.sin();
+        Coord::new(row.round() as isize, col.round() as isize)
+    };
+
+    let cell = |position: usize| cell_at(position as f64 / safe.size as f64);
+
+    // large dials share cells, so draw the ring itself rather than every position
+    let points = safe.size.min(RING_POINTS);
+    for point in 0..points {
+        grid.set(cell_at(point as f64 / points as f64), ".");
+    }
+    for &position in safe.watched.keys() {
+        grid.set(cell(position), "o");
+    }
+    grid.set(cell(step.start), "S");
+    let end = if step.start == step.end { "*" } else { "E" };
+    grid.set(cell(step.end), end);
+
+    grid.render(|c| c.copied().unwrap_or(" "))
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum Direction {
     Left,
     Right,
 }
 
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Left => write!(f, "L"),
+            Direction::Right => write!(f, "R"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct Instruction(Direction, usize);
 
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.0, self.1)
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let direction = match s.get(..1) {
+            Some("L") => Direction::Left,
+            Some("R") => Direction::Right,
+            _ => eyre::bail!("Invalid instruction: {s}"),
+        };
+        Ok(Instruction(direction, s[1..].parse()?))
+    }
+}
+
+/// An instruction for one dial of a [`Lock`], written like `A:L68`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LockInstruction {
+    dial: usize,
+    instruction: Instruction,
+}
+
+impl FromStr for LockInstruction {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (label, instruction) = s
+            .split_once(':')
+            .ok_or(eyre::eyre!("Missing dial label: {s}"))?;
+        let dial = match label.as_bytes() {
+            [c @ b'A'..=b'Z'] => (c - b'A') as usize,
+            _ => eyre::bail!("Invalid dial label: {label}"),
+        };
+        Ok(Self {
+            dial,
+            instruction: instruction.parse()?,
+        })
+    }
+}
+
+/// The letter naming a dial, `A` for the first
+fn dial_label(dial: usize) -> char {
+    (b'A' + dial as u8) as char
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const TEST_INPUT: &str = "L68\nL30\nR48\nL5\nR60\nL55\nL1\nL99\nR14\nL82\n";
+
     #[test]
     fn test_safe() {
-        let mut safe = Safe::new(50);
+        let mut safe = Safe::with_dial(DEFAULT_DIAL_SIZE, 50, [0]).unwrap();
 
         let overflows = safe.rotate(Direction::Left, 10);
         assert_eq!(overflows, 0);
@@ -98,6 +577,292 @@ mod tests {
         assert_eq!(safe.position, 30);
     }
 
+    #[test]
+    fn test_safe_matches_click_by_click() {
+        fn rotate_slowly(
+            size: usize,
+            position: usize,
+            target: usize,
+            direction: Direction,
+            clicks: usize,
+        ) -> (usize, usize) {
+            let mut position = position as isize;
+            let mut times_hit_target = 0;
+            for _ in 0..clicks {
+                match direction {
+                    Direction::Left => position -= 1,
+                    Direction::Right => position += 1,
+                }
+                position = position.rem_euclid(size as isize);
+                if position as usize == target {
+                    times_hit_target += 1;
+                }
+            }
+            (position as usize, times_hit_target)
+        }
+
+        for (size, target) in [(DEFAULT_DIAL_SIZE, 0), (7, 3), (1, 0)] {
+            for start in 0..size {
+                for clicks in [0, 1, 6, 7, 8, 49, 50, 99, 100, 101, 199, 200, 250, 1001] {
+                    for direction in [Direction::Left, Direction::Right] {
+                        let mut safe = Safe::with_dial(size, start, [target]).unwrap();
+                        let hits = safe.rotate(direction, clicks);
+                        assert_eq!(
+                            (safe.position, hits),
+                            rotate_slowly(size, start, target, direction, clicks),
+                            "{direction:?}{clicks} from {start} on a dial of {size}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_safe_tallies() {
+        let mut safe = Safe::with_dial(10, 5, [0, 2, 9]).unwrap();
+        safe.rotate(Direction::Right, 7);
+        safe.rotate(Direction::Left, 13);
+
+        // 5 -R7-> 2 (via 9, 0) -L13-> 9 (via 0, 9, 2, 0)
+        assert_eq!(safe.position, 9);
+        assert_eq!(
+            safe.tallies(),
+            &BTreeMap::from([
+                (0, Tally { landings: 0, passes: 3 }),
+                (2, Tally { landings: 1, passes: 2 }),
+                (9, Tally { landings: 1, passes: 3 }),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_safe_invalid_dial() {
+        assert!(Safe::with_dial(0, 0, [0]).is_err());
+        assert!(Safe::with_dial(10, 10, [0]).is_err());
+        assert!(Safe::with_dial(10, 0, [3, 12]).is_err());
+    }
+
+    #[test]
+    fn test_safe_huge_rotation() {
+        let mut safe = Safe::with_dial(DEFAULT_DIAL_SIZE, 50, [0]).unwrap();
+        assert_eq!(safe.rotate(Direction::Right, 1_000_000_000), 10_000_000);
+        assert_eq!(safe.position, 50);
+
+        assert_eq!(safe.rotate(Direction::Left, 1_000_000_000), 10_000_000);
+        assert_eq!(safe.position, 50);
+
+        assert_eq!(safe.rotate(Direction::Right, usize::MAX), usize::MAX / 100);
+        assert_eq!(safe.position, 65);
+    }
+
+    #[test]
+    fn test_trace() {
+        let instructions = parse_input(TEST_INPUT.to_string());
+        let mut safe = Safe::with_dial(DEFAULT_DIAL_SIZE, 50, [0]).unwrap();
+        let trace = safe.trace(&instructions);
+
+        assert_eq!(
+            trace[0],
+            TraceStep {
+                instruction: Instruction(Direction::Left, 68),
+                start: 50,
+                end: 82,
+                passes: 1,
+                landed: false,
+            }
+        );
+        assert_eq!(trace[2].end, 0);
+        assert!(trace[2].landed);
+        assert_eq!(trace.iter().filter(|s| s.landed).count(), 3);
+        assert_eq!(trace.iter().map(|s| s.passes).sum::<usize>(), 6);
+
+        let csv = trace_csv(&trace);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("instruction,start,end,passes,landed"));
+        assert_eq!(lines.next(), Some("L68,50,82,1,false"));
+        assert_eq!(lines.count(), 9);
+
+        let json = trace_json(&trace);
+        assert!(json.starts_with(
+            "[{\"instruction\":\"L68\",\"start\":50,\"end\":82,\"passes\":1,\"landed\":false},"
+        ));
+    }
+
+    #[test]
+    fn test_render_dial() {
+        let safe = Safe::with_dial(DEFAULT_DIAL_SIZE, 50, [0]).unwrap();
+        let step = TraceStep {
+            instruction: Instruction(Direction::Right, 25),
+            start: 50,
+            end: 75,
+            passes: 0,
+            landed: false,
+        };
+        let dial = render_dial(&safe, &step);
+        let lines = dial.lines().collect::<Vec<_>>();
+
+        // 0 at the top, 50 at the bottom and 75 on the left
+        assert_eq!(lines[0].trim(), "....o....");
+        assert_eq!(lines.last().unwrap().trim(), "....S....");
+        assert!(lines[6].starts_with("E"));
+        assert!(lines[6].contains('+'));
+
+        // a huge dial draws a solid ring without visiting every position
+        let huge = Safe::with_dial(1 << 40, 0, [1 << 39]).unwrap();
+        let step = TraceStep {
+            instruction: Instruction(Direction::Left, 1 << 38),
+            start: 0,
+            end: 3 << 38,
+            passes: 0,
+            landed: false,
+        };
+        let dial = render_dial(&huge, &step);
+        let lines = dial.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0].trim(), ".....S.....");
+        assert_eq!(lines.last().unwrap().trim(), ".....o.....");
+        assert!(lines[6].starts_with("E"));
+    }
+
+    #[test]
+    fn test_parse_lock_instruction() {
+        assert_eq!(
+            "A:L68".parse::<LockInstruction>().unwrap(),
+            LockInstruction {
+                dial: 0,
+                instruction: Instruction(Direction::Left, 68)
+            }
+        );
+        assert_eq!(
+            "C:R3".parse::<LockInstruction>().unwrap(),
+            LockInstruction {
+                dial: 2,
+                instruction: Instruction(Direction::Right, 3)
+            }
+        );
+
+        for invalid in ["L68", "a:L1", "AB:R1", "A:X3", "A:L", "A:"] {
+            assert!(invalid.parse::<LockInstruction>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_lock_independent_dials() {
+        let input = TEST_INPUT
+            .lines()
+            .enumerate()
+            .map(|(i, line)| format!("{}:{line}", dial_label(i % 2)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let instructions = parse_lock_input(&input).unwrap();
+        let mut lock = Lock::new(2, DEFAULT_DIAL_SIZE, 50, vec![0], false).unwrap();
+        let passes = instructions.iter().map(|i| lock.rotate(*i)).sum::<usize>();
+
+        for (dial, safe) in lock.dials.iter().enumerate() {
+            let mut expected = Safe::with_dial(DEFAULT_DIAL_SIZE, 50, [0]).unwrap();
+            for instruction in instructions.iter().filter(|i| i.dial == dial) {
+                expected.rotate(instruction.instruction.0, instruction.instruction.1);
+            }
+            assert_eq!(safe.position, expected.position);
+            assert_eq!(safe.tallies(), expected.tallies());
+        }
+
+        let total = lock.dials.iter().map(|d| d.tallies()[&0].passes).sum::<usize>();
+        assert_eq!(passes, total);
+    }
+
+    #[test]
+    fn test_lock_geared() {
+        let mut lock = Lock::new(3, 10, 0, vec![0], true).unwrap();
+        let reading = |lock: &Lock| {
+            lock.dials
+                .iter()
+                .rev()
+                .fold(0, |acc, dial| acc * 10 + dial.position)
+        };
+
+        lock.rotate("A:R25".parse().unwrap());
+        lock.rotate("A:R999".parse().unwrap());
+        assert_eq!(reading(&lock), 24);
+
+        // borrowing works like an odometer running backwards
+        lock.rotate("A:L30".parse().unwrap());
+        assert_eq!(reading(&lock), 994);
+
+        lock.rotate("B:R1".parse().unwrap());
+        assert_eq!(reading(&lock), 4);
+    }
+
+    #[test]
+    fn test_best_start() {
+        let instructions = parse_input(TEST_INPUT.to_string());
+
+        let (start, landings) =
+            Safe::best_start(DEFAULT_DIAL_SIZE, &[0], &instructions, Goal::Landings).unwrap();
+        assert_eq!((start, landings), (50, 3));
+
+        let mut safe = Safe::with_dial(DEFAULT_DIAL_SIZE, start, [0]).unwrap();
+        let trace = safe.trace(&instructions);
+        assert_eq!(trace.iter().filter(|s| s.landed).count(), landings);
+
+        // no start can beat the best one
+        for other in 0..DEFAULT_DIAL_SIZE {
+            let mut safe = Safe::with_dial(DEFAULT_DIAL_SIZE, other, [0]).unwrap();
+            let trace = safe.trace(&instructions);
+            let other_landings = trace.iter().filter(|s| s.landed).count();
+            assert!(other_landings < landings || (other_landings == landings && other >= start));
+        }
+
+        let best = Safe::best_start(DEFAULT_DIAL_SIZE, &[0], &instructions, Goal::Passes);
+        assert_eq!(best.unwrap(), (37, 6));
+    }
+
+    #[test]
+    fn test_shortest_sequence() {
+        let safe = Safe::with_dial(DEFAULT_DIAL_SIZE, 50, [0]).unwrap();
+        assert_eq!(
+            safe.shortest_sequence(32, 3),
+            Some(vec![Instruction(Direction::Right, 282)])
+        );
+        assert_eq!(
+            safe.shortest_sequence(40, 0),
+            Some(vec![Instruction(Direction::Left, 10)])
+        );
+        assert_eq!(safe.shortest_sequence(50, 0), Some(vec![]));
+        assert_eq!(
+            safe.shortest_sequence(50, 1),
+            Some(vec![Instruction(Direction::Right, 100)])
+        );
+
+        // landing on zero always counts as passing it
+        assert_eq!(safe.shortest_sequence(0, 0), None);
+        assert_eq!(safe.shortest_sequence(100, 0), None);
+        // too many passes to fit in a single instruction
+        assert_eq!(safe.shortest_sequence(40, usize::MAX), None);
+    }
+
+    #[test]
+    fn test_shortest_sequence_replays() {
+        let size = 7;
+        for start in 0..size {
+            let safe = Safe::with_dial(size, start, [0]).unwrap();
+            for target in 0..size {
+                for passes in 0..4 {
+                    let Some(sequence) = safe.shortest_sequence(target, passes) else {
+                        assert!(target == 0 && passes == 0);
+                        continue;
+                    };
+                    assert!(sequence.len() <= 1);
+
+                    let mut replay = Safe::with_dial(size, start, [0]).unwrap();
+                    let trace = replay.trace(&sequence);
+                    assert_eq!(replay.position, target);
+                    assert_eq!(trace.iter().map(|s| s.passes).sum::<usize>(), passes);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_parse_input() {
         let input = "L68\nL30\nR48\nL5\nR60\nL55\nL1\nL99\nR14\nL82\n".to_string();
@@ -123,7 +888,7 @@ mod tests {
     fn test_part1() {
         let input = "L68\nL30\nR48\nL5\nR60\nL55\nL1\nL99\nR14\nL82\n".to_string();
         let instructions = parse_input(input);
-        let mut safe = Safe::new(50);
+        let mut safe = Safe::with_dial(DEFAULT_DIAL_SIZE, 50, [0]).unwrap();
         let mut zero_counts = 0;
         for instruction in instructions {
             safe.rotate(instruction.0, instruction.1);
@@ -139,7 +904,7 @@ mod tests {
     fn test_part2() {
         let input = "L68\nL30\nR48\nL5\nR60\nL55\nL1\nL99\nR14\nL82\n".to_string();
         let instructions = parse_input(input);
-        let mut safe = Safe::new(50);
+        let mut safe = Safe::with_dial(DEFAULT_DIAL_SIZE, 50, [0]).unwrap();
         let mut zero_counts = 0;
         for instruction in instructions {
             zero_counts += safe.rotate(instruction.0, instruction.1);
@@ -1,76 +1,585 @@
-use std::str::FromStr;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use clap::{Parser, ValueEnum};
+use eyre::WrapErr;
+
+use crate::util::intervals::{self, Interval, IntervalSet, Overlap};
+
+#[derive(Parser)]
+struct Options {
+    /// Count IDs matching this rule instead of solving both parts
+    #[arg(long, value_enum)]
+    rule: Option<RulePreset>,
+    /// Only count blocks repeated exactly this many times
+    #[arg(long, conflicts_with_all = ["min_repeats", "max_repeats"])]
+    repeats: Option<usize>,
+    /// Only count blocks repeated at least this many times
+    #[arg(long)]
+    min_repeats: Option<usize>,
+    /// Only count blocks repeated at most this many times
+    #[arg(long)]
+    max_repeats: Option<usize>,
+    /// The base the IDs are written in
+    #[arg(long, default_value_t = 10)]
+    base: u32,
+    /// Only look at IDs within START-END
+    #[arg(long, value_name = "START-END")]
+    within: Option<Interval<u128>>,
+    /// Also print how many invalid IDs there are, and the smallest and largest
+    #[arg(long)]
+    stats: bool,
+    /// Also print the Nth smallest invalid ID
+    #[arg(long, value_name = "N")]
+    nth: Option<NonZeroUsize>,
+    /// Sum the invalid IDs using this many threads
+    #[arg(long, value_name = "N", default_value = "1")]
+    jobs: NonZeroUsize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RulePreset {
+    /// Part 1: a block of digits repeated exactly twice
+    Twice,
+    /// Part 2: a block of digits repeated at least twice
+    AtLeastTwice,
+    /// IDs that read the same forwards and backwards
+    Palindrome,
+}
+
+impl Options {
+    /// The rule described by the options, if they describe one
+    fn rule(&self) -> eyre::Result<Option<RepeatRule>> {
+        let counted =
+            self.repeats.is_some() || self.min_repeats.is_some() || self.max_repeats.is_some();
+        let customized = counted || self.base != 10;
+        if self.rule.is_none() && !customized {
+            return Ok(None);
+        }
+
+        let (min, max) = match self.rule {
+            Some(RulePreset::Palindrome) => {
+                if counted {
+                    eyre::bail!("Palindromes don't have a repeat count");
+                }
+                return RepeatRule::palindrome(self.base).map(Some);
+            }
+            Some(RulePreset::Twice) => (2, Some(2)),
+            Some(RulePreset::AtLeastTwice) | None => (2, None),
+        };
+
+        let (min, max) = match self.repeats {
+            Some(repeats) => (repeats, Some(repeats)),
+            None => (
+                self.min_repeats.unwrap_or(min),
+                self.max_repeats.or(max),
+            ),
+        };
+        RepeatRule::repeated(min, max, self.base).map(Some)
+    }
+}
+
+pub(crate) fn run(input: String, args: &[String]) -> eyre::Result<()> {
+    let options: Options = crate::parse_day_options("02", args);
+    let (product_ranges, overlaps) = parse_input(&input)?;
+
+    for overlap in &overlaps {
+        println!(
+            "Ranges {} and {} overlap on {}",
+            overlap.first, overlap.second, overlap.shared
+        );
+    }
+
+    let rules = match options.rule()? {
+        Some(rule) => vec![(rule.to_string(), rule)],
+        None => vec![
+            ("Part 1".to_string(), RepeatRule::TWICE),
+            ("Part 2".to_string(), RepeatRule::AT_LEAST_TWICE),
+        ],
+    };
+
+    let product_ranges = match options.within {
+        Some(within) => product_ranges.intersection(&IntervalSet::from_iter([within])),
+        None => product_ranges,
+    };
+    let product_ranges = product_ranges.intervals();
+
+    for (label, rule) in rules {
+        let wanted = options.nth.map(|n| n.get() - 1);
+        let mut nth_id = None;
+        let summary = match options.jobs.get() {
+            1 => Summary::of(
+                rule.ids_in_ranges(product_ranges)
+                    .enumerate()
+                    .inspect(|&(i, id)| {
+                        if Some(i) == wanted {
+                            nth_id = Some(id);
+                        }
+                    })
+                    .map(|(_, id)| id),
+            ),
+            jobs => {
+                nth_id = wanted.and_then(|i| rule.ids_in_ranges(product_ranges).nth(i));
+                parallel_summary(&rule, product_ranges, jobs)
+            }
+        };
+        println!("{label}: {}", summary.sum);
+
+        if options.stats {
+            match (summary.min, summary.max) {
+                (Some(min), Some(max)) => println!(
+                    "  {} invalid IDs, smallest {min}, largest {max}",
+                    summary.count
+                ),
+                _ => println!("  No invalid IDs"),
+            }
+        }
+
+        if let Some(n) = options.nth {
+            match nth_id {
+                Some(id) => println!("  Invalid ID #{n}: {id}"),
+                None => println!("  There are fewer than {n} invalid IDs"),
+            }
+        }
+    }
 
-pub(crate) fn run(input: String) -> eyre::Result<()> {
+    Ok(())
+}
+
+/// Parse the comma separated product ranges into a set, along with any overlaps between them
+fn parse_input(input: &str) -> eyre::Result<(IntervalSet<u128>, Vec<Overlap<u128>>)> {
     let product_ranges = input
+        .trim()
         .split(',')
-        .map(|s| s.parse::<ProductRange>().unwrap())
-        .collect::<Vec<_>>();
+        .map(|s| {
+            s.parse::<Interval<u128>>()
+                .wrap_err_with(|| format!("Invalid product range {:?}", s.trim()))
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let overlaps = intervals::overlaps(&product_ranges);
+    Ok((product_ranges.into_iter().collect(), overlaps))
+}
 
-    let invalid_ids = product_ranges
-        .iter()
-        .flat_map(|pr| pr.invalid_ids_type1())
-        .collect::<Vec<_>>();
+/// How many chunks to cut the ranges into for each thread, so uneven chunks balance out
+const CHUNKS_PER_JOB: usize = 8;
+
+/// Summarize every ID in `ranges` matching `rule`, spreading the work over `jobs` threads
+///
+/// The ranges are cut into chunks of similar width, and each worker keeps
+/// claiming the next unclaimed chunk and adding to its own summary until none are left.
+fn parallel_summary(rule: &RepeatRule, ranges: &[Interval<u128>], jobs: usize) -> Summary {
+    let chunks = split_ranges(ranges, jobs * CHUNKS_PER_JOB);
+    let next_chunk = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        let workers = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut summary = Summary::default();
+                    while let Some(chunk) = chunks.get(next_chunk.fetch_add(1, Ordering::Relaxed)) {
+                        summary = summary.merge(Summary::of(rule.ids_in(chunk)));
+                    }
+                    summary
+                })
+            })
+            .collect::<Vec<_>>();
 
-    let sum = invalid_ids.iter().sum::<u128>();
-    println!("Part 1: {}", sum);
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("worker thread panicked"))
+            .fold(Summary::default(), Summary::merge)
+    })
+}
 
-    let invalid_ids = product_ranges
+/// Cut the ranges into roughly `count` chunks of similar width, in order
+fn split_ranges(ranges: &[Interval<u128>], count: usize) -> Vec<Interval<u128>> {
+    let total_width = ranges
         .iter()
-        .flat_map(|pr| pr.invalid_ids_type2())
-        .collect::<Vec<_>>();
+        .map(|range| (range.end() - range.start()).saturating_add(1))
+        .fold(0u128, u128::saturating_add);
+    let chunk_width = (total_width / count as u128).max(1);
+
+    let mut chunks = Vec::new();
+    for range in ranges {
+        let mut start = range.start();
+        loop {
+            let end = start.saturating_add(chunk_width - 1).min(range.end());
+            chunks.push(Interval::new(start, end).expect("chunks never run backwards"));
+            if end == range.end() {
+                break;
+            }
+            start = end + 1;
+        }
+    }
+    chunks
+}
 
-    let sum = invalid_ids.iter().sum::<u128>();
-    println!("Part 2: {}", sum);
+/// The count, sum and bounds of a stream of IDs, gathered in a single pass
+///
+/// The count and sum saturate at `u128::MAX` rather than overflowing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Summary {
+    count: u128,
+    sum: u128,
+    min: Option<u128>,
+    max: Option<u128>,
+}
 
-    Ok(())
+impl Summary {
+    fn of(ids: impl IntoIterator<Item = u128>) -> Self {
+        ids.into_iter().fold(Self::default(), Self::add)
+    }
+
+    /// Add one more ID to the summary
+    fn add(self, id: u128) -> Self {
+        self.merge(Self {
+            count: 1,
+            sum: id,
+            min: Some(id),
+            max: Some(id),
+        })
+    }
+
+    /// Combine the summaries of two streams
+    fn merge(self, other: Self) -> Self {
+        Self {
+            count: self.count.saturating_add(other.count),
+            sum: self.sum.saturating_add(other.sum),
+            min: self.min.into_iter().chain(other.min).min(),
+            max: self.max.into_iter().chain(other.max).max(),
+        }
+    }
 }
 
-#[derive(Debug)]
-struct ProductRange {
-    start: u128,
-    end: u128,
+/// A rule deciding which IDs are invalid, based on the digits they're written with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RepeatRule {
+    kind: RuleKind,
+    base: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleKind {
+    /// Made ONLY of some block of digits repeated between `min` and `max` times
+    Repeated { min: usize, max: Option<usize> },
+    /// Reads the same forwards and backwards
+    Palindrome,
 }
 
-impl ProductRange {
-    fn new(start: u128, end: u128) -> Self {
-        Self { start, end }
+impl RepeatRule {
+    /// Part 1: a block of digits repeated exactly twice
+    const TWICE: Self = Self {
+        kind: RuleKind::Repeated {
+            min: 2,
+            max: Some(2),
+        },
+        base: 10,
+    };
+
+    /// Part 2: a block of digits repeated at least twice
+    const AT_LEAST_TWICE: Self = Self {
+        kind: RuleKind::Repeated { min: 2, max: None },
+        base: 10,
+    };
+
+    fn repeated(min: usize, max: Option<usize>, base: u32) -> eyre::Result<Self> {
+        if min < 2 {
+            eyre::bail!("A block must be repeated at least twice, got {min}");
+        }
+        if let Some(max) = max.filter(|max| *max < min) {
+            eyre::bail!("The maximum repeat count {max} is below the minimum {min}");
+        }
+        Self::with_base(RuleKind::Repeated { min, max }, base)
+    }
+
+    fn palindrome(base: u32) -> eyre::Result<Self> {
+        Self::with_base(RuleKind::Palindrome, base)
     }
 
-    fn invalid_ids_type1(&self) -> Vec<u128> {
-        let mut invalid = Vec::with_capacity(2);
+    fn with_base(kind: RuleKind, base: u32) -> eyre::Result<Self> {
+        if !(2..=36).contains(&base) {
+            eyre::bail!("Bases must be between 2 and 36, got {base}");
+        }
+        Ok(Self { kind, base })
+    }
 
-        for id in self.start..=self.end {
-            if is_repeated_digits_type1(&id.to_string()) {
-                invalid.push(id);
+    fn allows_repeats(&self, repeats: usize) -> bool {
+        match self.kind {
+            RuleKind::Repeated { min, max } => {
+                repeats >= min && max.is_none_or(|max| repeats <= max)
             }
+            RuleKind::Palindrome => false,
         }
+    }
+
+    /// Check a single ID against the rule, digit by digit
+    #[cfg(test)]
+    fn matches(&self, id: u128) -> bool {
+        let digits = digits(id, self.base);
+        match self.kind {
+            RuleKind::Repeated { .. } => (2..=digits.len())
+                .filter(|&repeats| digits.len().is_multiple_of(repeats))
+                .filter(|&repeats| self.allows_repeats(repeats))
+                .any(|repeats| {
+                    let block_length = digits.len() / repeats;
+                    digits
+                        .chunks(block_length)
+                        .all(|block| block == &digits[..block_length])
+                }),
+            RuleKind::Palindrome => digits.iter().eq(digits.iter().rev()),
+        }
+    }
 
-        invalid
+    /// Lazily iterate over every ID in `range` matching the rule, in ascending order
+    fn ids_in(&self, range: &Interval<u128>) -> RangeIds {
+        RangeIds::new(*self, range)
     }
 
-    fn invalid_ids_type2(&self) -> Vec<u128> {
-        // the ID is invalid if it is made ONLY of some sequence of digits repeated at least twice
-        let mut invalid = Vec::with_capacity(2);
+    /// Lazily iterate over every matching ID in `ranges`
+    ///
+    /// The IDs come out in ascending order as long as the ranges are sorted and
+    /// disjoint, as they are in an [`IntervalSet`].
+    fn ids_in_ranges<'a>(
+        &self,
+        ranges: &'a [Interval<u128>],
+    ) -> impl Iterator<Item = u128> + use<'a> {
+        let rule = *self;
+        ranges.iter().flat_map(move |range| rule.ids_in(range))
+    }
+}
 
-        for id in self.start..=self.end {
-            if is_repeated_digits_type2(&id.to_string()) {
-                invalid.push(id);
+impl fmt::Display for RepeatRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            RuleKind::Repeated { min, max: Some(max) } if min == max => {
+                write!(f, "Blocks repeated {min} times")?
+            }
+            RuleKind::Repeated { min, max: Some(max) } => {
+                write!(f, "Blocks repeated {min} to {max} times")?
             }
+            RuleKind::Repeated { min, max: None } => {
+                write!(f, "Blocks repeated at least {min} times")?
+            }
+            RuleKind::Palindrome => write!(f, "Palindromes")?,
+        }
+        if self.base != 10 {
+            write!(f, " in base {}", self.base)?;
         }
+        Ok(())
+    }
+}
+
+/// The most block lengths one ID length can have: 120 digits (in base 2) has 15
+const MAX_PERIODS: usize = 15;
+
+/// Lazy iterator over the IDs in one range matching a rule, see [`RepeatRule::ids_in`]
+///
+/// Rather than scanning the range, this builds the IDs directly, one length at
+/// a time. A block `p` of `b` digits repeated `r` times is `p * m`, where `m` is
+/// the repdigit multiplier (1001 for b=3, r=2; 10101 for b=2, r=3), so the work
+/// done depends on the number of matches, not the width of the range.
+struct RangeIds {
+    rule: RepeatRule,
+    start: u128,
+    end: u128,
+    length: usize,
+    max_length: usize,
+    current: LengthIds,
+}
 
-        invalid
+/// The IDs of a single length still to be produced
+///
+/// The periods are kept inline rather than boxed so iterating never allocates.
+#[allow(clippy::large_enum_variant)]
+enum LengthIds {
+    /// The next ID for each allowed block length, merged smallest first
+    Repeated {
+        periods: [Period; MAX_PERIODS],
+        count: usize,
+    },
+    /// The first halves `next..last` of the palindromes in range
+    Palindrome { next: u128, last: u128 },
+}
+
+/// The IDs `block * multiplier` for every `block` in `next..=last`
+#[derive(Debug, Clone, Copy, Default)]
+struct Period {
+    next: u128,
+    last: u128,
+    multiplier: u128,
+}
+
+impl Period {
+    fn peek(&self) -> Option<u128> {
+        (self.next <= self.last).then(|| self.next * self.multiplier)
     }
 }
 
-impl FromStr for ProductRange {
-    type Err = eyre::Error;
+impl RangeIds {
+    fn new(rule: RepeatRule, range: &Interval<u128>) -> Self {
+        let base = rule.base as u128;
+        let length = digit_count(range.start(), base);
+        let mut ids = Self {
+            rule,
+            start: range.start(),
+            end: range.end(),
+            length,
+            max_length: digit_count(range.end(), base),
+            current: LengthIds::Palindrome { next: 0, last: 0 },
+        };
+        ids.current = ids.length_ids();
+        ids
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (start, end) = s.split_once('-').ok_or(eyre::eyre!("Invalid range"))?;
-        Ok(Self::new(start.parse()?, end.parse()?))
+    fn length_ids(&self) -> LengthIds {
+        let (start, end, length) = (self.start, self.end, self.length);
+        let base = self.rule.base as u128;
+
+        match self.rule.kind {
+            RuleKind::Repeated { .. } => {
+                let mut periods = [Period::default(); MAX_PERIODS];
+                let mut count = 0;
+                for repeats in (2..=length)
+                    .filter(|&r| length.is_multiple_of(r) && self.rule.allows_repeats(r))
+                {
+                    let block_length = (length / repeats) as u32;
+                    let Some(multiplier) = repdigit_multiplier(block_length, repeats, base) else {
+                        continue;
+                    };
+
+                    // blocks can't start with a zero
+                    let smallest_block = base.pow(block_length - 1);
+                    let largest_block = base.pow(block_length) - 1;
+                    periods[count] = Period {
+                        next: start.div_ceil(multiplier).max(smallest_block),
+                        last: (end / multiplier).min(largest_block),
+                        multiplier,
+                    };
+                    count += 1;
+                }
+                LengthIds::Repeated { periods, count }
+            }
+            RuleKind::Palindrome => {
+                // a palindrome is determined by its first half, and bigger halves
+                // make bigger palindromes, so the halves in range can be found by
+                // binary search
+                let half_length = length.div_ceil(2) as u32;
+                // only the single digit palindrome 0 starts with a zero
+                let smallest_half = if length == 1 {
+                    0
+                } else {
+                    base.pow(half_length - 1)
+                };
+                let largest_half = base.pow(half_length) - 1;
+
+                // a palindrome that overflows is bigger than any end
+                let palindrome = |half| palindrome_from_half(half, length, base);
+                let next = partition_point(smallest_half, largest_half, |half| {
+                    palindrome(half).is_some_and(|p| p < start)
+                });
+                let last = partition_point(next, largest_half, |half| {
+                    palindrome(half).is_some_and(|p| p <= end)
+                });
+                LengthIds::Palindrome { next, last }
+            }
+        }
+    }
+}
+
+impl Iterator for RangeIds {
+    type Item = u128;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let id = match &mut self.current {
+                LengthIds::Repeated { periods, count } => {
+                    let periods = &mut periods[..*count];
+                    let id = periods.iter().filter_map(Period::peek).min();
+                    // IDs like 1111 repeat with more than one period
+                    for period in periods.iter_mut() {
+                        if id.is_some() && period.peek() == id {
+                            period.next += 1;
+                        }
+                    }
+                    id
+                }
+                LengthIds::Palindrome { next, last } => (*next < *last).then(|| {
+                    *next += 1;
+                    palindrome_from_half(*next - 1, self.length, self.rule.base as u128)
+                        .expect("halves in range make palindromes in range")
+                }),
+            };
+
+            if id.is_some() {
+                return id;
+            }
+            if self.length >= self.max_length {
+                return None;
+            }
+            self.length += 1;
+            self.current = self.length_ids();
+        }
+    }
+}
+
+/// The multiplier that repeats a block of `block_length` digits `repeats` times
+fn repdigit_multiplier(block_length: u32, repeats: usize, base: u128) -> Option<u128> {
+    let shift = base.checked_pow(block_length)?;
+    let mut multiplier: u128 = 0;
+    for _ in 0..repeats {
+        multiplier = multiplier.checked_mul(shift)?.checked_add(1)?;
+    }
+    Some(multiplier)
+}
+
+/// Mirror `half` into a palindrome of `length` digits
+fn palindrome_from_half(half: u128, length: usize, base: u128) -> Option<u128> {
+    let mut palindrome = half;
+    let mut rest = if length % 2 == 1 { half / base } else { half };
+    for _ in 0..length / 2 {
+        palindrome = palindrome.checked_mul(base)?.checked_add(rest % base)?;
+        rest /= base;
+    }
+    Some(palindrome)
+}
+
+/// The first value in `lo..=hi` where `pred` stops holding, or `hi + 1` if it always does
+fn partition_point(mut lo: u128, hi: u128, pred: impl Fn(u128) -> bool) -> u128 {
+    let mut hi = hi + 1;
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+fn digit_count(n: u128, base: u128) -> usize {
+    n.checked_ilog(base).map_or(1, |log| log as usize + 1)
+}
+
+/// The digits of `n` in `base`, most significant first
+#[cfg(test)]
+fn digits(mut n: u128, base: u32) -> Vec<u32> {
+    let mut digits = Vec::new();
+    loop {
+        digits.push((n % base as u128) as u32);
+        n /= base as u128;
+        if n == 0 {
+            break;
+        }
     }
+    digits.reverse();
+    digits
 }
 
+/// The original string check for IDs made of a sequence repeated exactly twice
+#[cfg(test)]
 fn is_repeated_digits_type1(s: &str) -> bool {
     if !s.len().is_multiple_of(2) {
         return false;
@@ -82,6 +591,8 @@ fn is_repeated_digits_type1(s: &str) -> bool {
     first_half == second_half
 }
 
+/// The original string check for IDs made of a sequence repeated at least twice
+#[cfg(test)]
 fn is_repeated_digits_type2(s: &str) -> bool {
     let possible_substr_lengths = (1..=s.len() / 2).collect::<Vec<_>>();
     for substr_length in possible_substr_lengths {
@@ -109,61 +620,249 @@ mod tests {
 
     #[test]
     fn test_invalid_ids_type1() {
-        assert_eq!(is_repeated_digits_type1("1111"), true);
-        assert_eq!(is_repeated_digits_type1("1212"), true);
-        assert_eq!(is_repeated_digits_type1("123123"), true);
-        assert_eq!(is_repeated_digits_type1("1231234"), false);
+        assert!(is_repeated_digits_type1("1111"));
+        assert!(is_repeated_digits_type1("1212"));
+        assert!(is_repeated_digits_type1("123123"));
+        assert!(!is_repeated_digits_type1("1231234"));
+
+        let rule = RepeatRule::TWICE;
+        assert!(rule.matches(1111));
+        assert!(rule.matches(1212));
+        assert!(rule.matches(123123));
+        assert!(!rule.matches(1231234));
     }
 
     #[test]
     fn test_invalid_ids_type2() {
-        assert_eq!(is_repeated_digits_type2("1111"), true);
-        assert_eq!(is_repeated_digits_type2("1212"), true);
-        assert_eq!(is_repeated_digits_type2("123123"), true);
-        assert_eq!(is_repeated_digits_type2("1231234"), false);
+        assert!(is_repeated_digits_type2("1111"));
+        assert!(is_repeated_digits_type2("1212"));
+        assert!(is_repeated_digits_type2("123123"));
+        assert!(!is_repeated_digits_type2("1231234"));
+
+        assert!(is_repeated_digits_type2("11111"));
+        assert!(is_repeated_digits_type2("123123123"));
+
+        let rule = RepeatRule::AT_LEAST_TWICE;
+        assert!(rule.matches(1111));
+        assert!(rule.matches(1212));
+        assert!(rule.matches(123123));
+        assert!(!rule.matches(1231234));
+
+        assert!(rule.matches(11111));
+        assert!(rule.matches(123123123));
+    }
 
-        assert_eq!(is_repeated_digits_type2("11111"), true);
-        assert_eq!(is_repeated_digits_type2("123123123"), true);
+    #[test]
+    fn test_presets_match_original_checks() {
+        let presets = [
+            (RepeatRule::TWICE, is_repeated_digits_type1 as fn(&str) -> bool),
+            (RepeatRule::AT_LEAST_TWICE, is_repeated_digits_type2),
+        ];
+        let windows = [(1, 2000), (9_990, 101_000), (9_999_000, 10_001_000)];
+        for (rule, original) in presets {
+            for (start, end) in windows {
+                let range = Interval::new(start, end).unwrap();
+                let expected = (start..=end)
+                    .filter(|id| original(&id.to_string()))
+                    .collect::<Vec<_>>();
+                let ids = rule.ids_in(&range).collect::<Vec<_>>();
+                assert_eq!(ids, expected, "{rule} in {start}-{end}");
+                assert!(ids.iter().all(|&id| rule.matches(id)));
+            }
+        }
     }
 
     #[test]
-    fn test_find_invalid_ids_type1() {
-        println!("TEST_INPUT: {}", TEST_INPUT);
+    fn test_custom_rules() {
+        let thrice = RepeatRule::repeated(3, Some(3), 10).unwrap();
+        assert!(thrice.matches(121212));
+        assert!(!thrice.matches(1212));
+        assert!(!thrice.matches(11111111));
+
+        let up_to_three = RepeatRule::repeated(2, Some(3), 10).unwrap();
+        assert!(up_to_three.matches(1111));
+        assert!(up_to_three.matches(777));
+        assert!(!up_to_three.matches(77777));
+
+        // 0b1010 and 0b111111
+        let binary = RepeatRule::repeated(2, None, 2).unwrap();
+        assert!(binary.matches(10));
+        assert!(binary.matches(63));
+        assert!(!binary.matches(11));
+
+        let palindrome = RepeatRule::palindrome(10).unwrap();
+        assert!(palindrome.matches(7));
+        assert!(palindrome.matches(12321));
+        assert!(!palindrome.matches(12312));
+
+        assert!(RepeatRule::repeated(1, None, 10).is_err());
+        assert!(RepeatRule::repeated(3, Some(2), 10).is_err());
+        assert!(RepeatRule::palindrome(1).is_err());
+        assert!(RepeatRule::palindrome(37).is_err());
+    }
 
-        let product_ranges = TEST_INPUT
-            .split(',')
-            .map(|s| s.parse::<ProductRange>().unwrap())
-            .collect::<Vec<_>>();
+    #[test]
+    fn test_ids_between_match_scan() {
+        let rules = [
+            RepeatRule::TWICE,
+            RepeatRule::AT_LEAST_TWICE,
+            RepeatRule::repeated(3, Some(4), 10).unwrap(),
+            RepeatRule::repeated(2, None, 2).unwrap(),
+            RepeatRule::repeated(2, Some(2), 16).unwrap(),
+            RepeatRule::palindrome(10).unwrap(),
+            RepeatRule::palindrome(3).unwrap(),
+        ];
+        let windows = [(0, 2000), (9_990, 101_000), (123_000, 124_500), (9_999_000, 10_001_000)];
+        for rule in rules {
+            for (start, end) in windows {
+                let range = Interval::new(start, end).unwrap();
+                let scan = (start..=end)
+                    .filter(|id| rule.matches(*id))
+                    .collect::<Vec<_>>();
+                let ids = rule.ids_in(&range).collect::<Vec<_>>();
+                assert_eq!(ids, scan, "{rule} in {start}-{end}");
+            }
+        }
+    }
 
-        let invalid_ids = product_ranges
-            .iter()
-            .flat_map(|pr| pr.invalid_ids_type1())
-            .collect::<Vec<_>>();
+    #[test]
+    fn test_repeated_digit_ids_wide_range() {
+        let range = Interval::new(1, 10u128.pow(10)).unwrap();
+        let ids = RepeatRule::TWICE.ids_in(&range).collect::<Vec<_>>();
+
+        // 9 two digit IDs, 90 four digit IDs, ... up to 5 digit blocks
+        let expected = (1..=5).map(|b| 9 * 10usize.pow(b - 1)).sum::<usize>();
+        assert_eq!(ids.len(), expected);
+        assert_eq!(ids[..3], [11, 22, 33]);
+        assert_eq!(ids.last(), Some(&9_999_999_999));
+
+        let range = Interval::new(10u128.pow(30), 10u128.pow(30) + 10u128.pow(16)).unwrap();
+        assert_eq!(RepeatRule::AT_LEAST_TWICE.ids_in(&range).next(), None);
+        let top = Interval::new(u128::MAX - 10u128.pow(20), u128::MAX).unwrap();
+        assert_eq!(RepeatRule::AT_LEAST_TWICE.ids_in(&top).next(), None);
+
+        let palindromes = RepeatRule::palindrome(10).unwrap();
+        assert!(palindromes.ids_in(&top).all(|id| palindromes.matches(id)));
+    }
 
+    #[test]
+    fn test_ids_in_ranges_lazily() {
+        // far too many IDs to ever collect
+        let ranges = [Interval::new(1, 10u128.pow(30)).unwrap()];
+
+        // 9999 IDs with up to 8 digits come first
+        let mut ids = RepeatRule::TWICE.ids_in_ranges(&ranges);
+        assert_eq!(ids.nth(9_998), Some(99_999_999));
+        assert_eq!(ids.next(), Some(1_000_010_000));
+
+        let ranges = [
+            Interval::new(1, 100).unwrap(),
+            Interval::new(1000, 1200).unwrap(),
+        ];
+        let summary = Summary::of(RepeatRule::AT_LEAST_TWICE.ids_in_ranges(&ranges));
         assert_eq!(
-            invalid_ids,
-            vec![11, 22, 99, 1010, 1188511885, 222222, 446446, 38593859]
+            summary,
+            Summary {
+                count: 11,
+                sum: 495 + 1010 + 1111,
+                min: Some(11),
+                max: Some(1111),
+            }
         );
+        assert_eq!(Summary::of([]), Summary::default());
+
+        // sums too large for a u128 saturate instead of overflowing
+        let summary = Summary::of([u128::MAX - 1, 2, 3]);
+        assert_eq!((summary.count, summary.sum), (3, u128::MAX));
     }
 
     #[test]
-    fn test_find_invalid_ids_type2() {
-        let product_ranges = TEST_INPUT
-            .split(',')
-            .map(|s| s.parse::<ProductRange>().unwrap())
-            .collect::<Vec<_>>();
-
-        let invalid_ids = product_ranges
-            .iter()
-            .flat_map(|pr| pr.invalid_ids_type2())
-            .collect::<Vec<_>>();
+    fn test_find_invalid_ids_type1() {
+        let (product_ranges, _) = parse_input(TEST_INPUT).unwrap();
+        assert_eq!(
+            RepeatRule::TWICE
+                .ids_in_ranges(product_ranges.intervals())
+                .collect::<Vec<_>>(),
+            vec![11, 22, 99, 1010, 222222, 446446, 38593859, 1188511885]
+        );
+    }
 
+    #[test]
+    fn test_find_invalid_ids_type2() {
+        let (product_ranges, _) = parse_input(TEST_INPUT).unwrap();
         assert_eq!(
-            invalid_ids,
+            RepeatRule::AT_LEAST_TWICE
+                .ids_in_ranges(product_ranges.intervals())
+                .collect::<Vec<_>>(),
             vec![
-                11, 22, 99, 111, 999, 1010, 1188511885, 222222, 446446, 38593859, 565656,
-                824824824, 2121212121
+                11, 22, 99, 111, 999, 1010, 222222, 446446, 565656, 38593859, 824824824,
+                1188511885, 2121212121
             ]
         );
     }
+
+    #[test]
+    fn test_split_ranges() {
+        let ranges = [
+            Interval::new(0, 99).unwrap(),
+            Interval::new(150, 155).unwrap(),
+            Interval::new(200, u128::MAX).unwrap(),
+        ];
+        for count in [1, 3, 10, 1000] {
+            let chunks = split_ranges(&ranges, count);
+            assert!(chunks.len() <= count + ranges.len());
+            assert_eq!(intervals::overlaps(&chunks), vec![]);
+
+            // consecutive chunks either touch or straddle a gap between ranges
+            assert_eq!(chunks.first().map(Interval::start), Some(0));
+            assert_eq!(chunks.last().map(Interval::end), Some(u128::MAX));
+            for pair in chunks.windows(2) {
+                let touching = pair[0].end() + 1 == pair[1].start();
+                assert!(touching || ranges.iter().any(|r| r.end() == pair[0].end()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_summary() {
+        let (mut ranges, _) = parse_input(TEST_INPUT).unwrap();
+        ranges.insert(Interval::new(10u128.pow(9), 10u128.pow(10)).unwrap());
+        let ranges = ranges.intervals();
+
+        let rules = [
+            RepeatRule::TWICE,
+            RepeatRule::AT_LEAST_TWICE,
+            RepeatRule::repeated(2, None, 2).unwrap(),
+            RepeatRule::palindrome(10).unwrap(),
+        ];
+        for rule in rules {
+            let sequential = Summary::of(rule.ids_in_ranges(ranges));
+            for jobs in [1, 2, 3, 8] {
+                let parallel = parallel_summary(&rule, ranges, jobs);
+                assert_eq!(parallel, sequential, "{rule} on {jobs} jobs");
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_input() {
+        // overlapping and touching ranges are only counted once
+        let (ranges, overlaps) = parse_input("10-30,25-40,95-115,116-120\n").unwrap();
+        assert_eq!(
+            ranges.intervals(),
+            [Interval::new(10, 40).unwrap(), Interval::new(95, 120).unwrap()]
+        );
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(overlaps[0].shared, Interval::new(25, 30).unwrap());
+        assert_eq!(
+            RepeatRule::TWICE
+                .ids_in_ranges(ranges.intervals())
+                .collect::<Vec<_>>(),
+            vec![11, 22, 33, 99]
+        );
+
+        let error = parse_input("10-30,40-25").unwrap_err();
+        assert!(format!("{error:#}").contains("40-25 starts after it ends"));
+        assert!(parse_input("10-30,").is_err());
+    }
 }
@@ -1,4 +1,42 @@
-pub(crate) fn run(input: String) -> eyre::Result<()> {
+use std::collections::HashSet;
+
+use clap::Parser;
+use num::BigUint;
+
+#[derive(Parser)]
+struct Options {
+    /// Make the smallest joltage instead of the largest
+    #[arg(long)]
+    minimize: bool,
+    /// Leave at least this many batteries off between two picked ones
+    #[arg(long, default_value_t = 0)]
+    min_gap: usize,
+    /// Never pick two neighboring batteries, the same as a gap of 1
+    #[arg(long)]
+    no_adjacent: bool,
+    /// Use a digit at most COUNT times, e.g. `--cap 9=2`
+    #[arg(long, value_name = "DIGIT=COUNT", value_parser = parse_cap)]
+    cap: Vec<(usize, usize)>,
+    /// Print every bank with the picked batteries highlighted
+    #[arg(long)]
+    show: bool,
+}
+
+fn parse_cap(s: &str) -> Result<(usize, usize), String> {
+    let (digit, count) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected DIGIT=COUNT, got {s:?}"))?;
+    let digit = digit
+        .parse::<usize>()
+        .ok()
+        .filter(|digit| *digit <= 9)
+        .ok_or_else(|| format!("{digit:?} isn't a digit"))?;
+    let count = count.parse().map_err(|e| format!("invalid count: {e}"))?;
+    Ok((digit, count))
+}
+
+pub(crate) fn run(input: String, args: &[String]) -> eyre::Result<()> {
+    let options: Options = crate::parse_day_options("03", args);
     let bank = input
         .lines()
         .map(|line| {
@@ -8,70 +46,232 @@ pub(crate) fn run(input: String) -> eyre::Result<()> {
         })
         .collect::<Vec<_>>();
 
-    let total_joltage = bank
-        .iter()
-        .map(|line| largest_joltage_pt1(line))
-        .sum::<u32>();
-    println!("Part 1: {}", total_joltage);
+    let mut solver = BankSolver {
+        goal: if options.minimize {
+            Goal::Minimize
+        } else {
+            Goal::Maximize
+        },
+        min_gap: options.min_gap,
+        no_adjacent: options.no_adjacent,
+        ..BankSolver::new(2)
+    };
+    for (digit, count) in options.cap {
+        solver.caps[digit] = Some(count);
+    }
 
-    let total_joltage = bank
-        .iter()
-        .map(|line| largest_joltage_pt2(line, 12))
-        .sum::<u128>();
-    println!("Part 2: {}", total_joltage);
+    for (part, length) in [(1, 2), (2, 12)] {
+        solver.length = length;
+        if options.show {
+            for line in &bank {
+                let selection = solver.select(line)?;
+                println!("{} {}", selection.render(line), selection.value);
+            }
+        }
+        println!("Part {part}: {}", solver.total_joltage(&bank)?);
+    }
 
     Ok(())
 }
 
-fn largest_joltage_pt1(bank: &[u32]) -> u32 {
-    let mut pairs = bank.iter().enumerate().collect::<Vec<_>>();
-    pairs.sort_by(|a, b| b.1.cmp(a.1));
-    let first = pairs
-        .iter().find(|(i, _)| *i != bank.len() - 1)
-        .unwrap();
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Goal {
+    #[default]
+    Maximize,
+    Minimize,
+}
+
+/// Picks `length` batteries from a bank, in order, to make the best joltage
+#[derive(Debug, Clone, Default)]
+struct BankSolver {
+    length: usize,
+    goal: Goal,
+    /// The fewest batteries left off between two picked ones
+    min_gap: usize,
+    no_adjacent: bool,
+    /// How many times each digit may be picked, if limited
+    caps: [Option<usize>; 10],
+}
+
+impl BankSolver {
+    fn new(length: usize) -> Self {
+        Self {
+            length,
+            ..Self::default()
+        }
+    }
+
+    fn gap(&self) -> usize {
+        self.min_gap.max(self.no_adjacent as usize)
+    }
+
+    fn total_joltage(&self, bank: &[Vec<u32>]) -> eyre::Result<BigUint> {
+        bank.iter().map(|line| self.joltage(line)).sum()
+    }
+
+    /// Pick the batteries making the best joltage
+    fn select(&self, bank: &[u32]) -> eyre::Result<Selection> {
+        let indices = self.pick(bank)?;
+        let digits = indices.iter().map(|&i| bank[i] as u8).collect::<Vec<_>>();
+        let value = BigUint::from_radix_be(&digits, 10)
+            .ok_or_else(|| eyre::eyre!("Batteries must be digits 0-9"))?;
+        Ok(Selection { indices, value })
+    }
+
+    /// The best number that can be made from the bank's digits
+    fn joltage(&self, bank: &[u32]) -> eyre::Result<BigUint> {
+        Ok(self.select(bank)?.value)
+    }
+
+    /// The indices of the picked batteries
+    fn pick(&self, bank: &[u32]) -> eyre::Result<Vec<usize>> {
+        if bank.len() < self.length {
+            eyre::bail!(
+                "A bank of {} batteries can't make a joltage of {} digits",
+                bank.len(),
+                self.length
+            );
+        }
+
+        if self.gap() == 0 && self.caps.iter().all(Option::is_none) {
+            return Ok(self.pick_unconstrained(bank));
+        }
+
+        let mut search = Search {
+            solver: self,
+            bank,
+            used: [0; 10],
+            picked: Vec::with_capacity(self.length),
+            failed: HashSet::new(),
+        };
+        if search.extend(0, self.length) {
+            Ok(search.picked)
+        } else {
+            eyre::bail!(
+                "No {} batteries in the bank fit the gap and digit limits",
+                self.length
+            )
+        }
+    }
+
+    /// Pick batteries with nothing but the goal to satisfy
+    ///
+    /// A digit is popped off the stack whenever a better one comes along, as
+    /// long as there are enough digits left to still fill every slot.
+    fn pick_unconstrained(&self, bank: &[u32]) -> Vec<usize> {
+        let better = |new: u32, old: u32| match self.goal {
+            Goal::Maximize => new > old,
+            Goal::Minimize => new < old,
+        };
 
-    let second = pairs.iter().find(|(i, _)| i > &first.0).unwrap();
+        let mut droppable = bank.len() - self.length;
+        let mut stack: Vec<usize> = Vec::with_capacity(bank.len());
+        for (i, &digit) in bank.iter().enumerate() {
+            while droppable > 0 && stack.last().is_some_and(|&top| better(digit, bank[top])) {
+                stack.pop();
+                droppable -= 1;
+            }
+            stack.push(i);
+        }
 
-    first.1 * 10 + second.1
+        stack.truncate(self.length);
+        stack
+    }
 }
 
-fn largest_joltage_pt2(bank: &[u32], length: usize) -> u128 {
-    let mut pairs = bank.iter().cloned().enumerate().collect::<Vec<_>>();
-    pairs.sort_by(|a, b| b.1.cmp(&a.1));
-    let digits = largest_joltage_pt2_helper(pairs, length, Vec::new());
-    let str = digits
-        .iter()
-        .fold(String::new(), |acc, x| format!("{acc}{x}"));
-    str.parse::<u128>().unwrap()
+/// The batteries picked from a bank, and the joltage they make
+///
+/// Joltages can be any number of digits long, so they're kept as big integers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Selection {
+    /// The picked positions in the bank, in increasing order
+    indices: Vec<usize>,
+    value: BigUint,
 }
 
-fn largest_joltage_pt2_helper(
-    pairs: Vec<(usize, u32)>,
-    length: usize,
-    mut acc: Vec<u32>,
-) -> Vec<u32> {
-    if length == 0 {
-        return acc;
+impl Selection {
+    /// Write out the bank with the picked batteries in bold green
+    fn render(&self, bank: &[u32]) -> String {
+        let mut picked = self.indices.iter().peekable();
+        bank.iter()
+            .enumerate()
+            .map(|(i, digit)| {
+                if picked.next_if_eq(&&i).is_some() {
+                    format!("\x1b[1;32m{digit}\x1b[0m")
+                } else {
+                    digit.to_string()
+                }
+            })
+            .collect()
     }
+}
 
-    let max_index = pairs.iter().map(|(i, _)| *i).max().unwrap();
-    let first = *pairs
-        .iter().find(|(i, _)| *i <= max_index - length + 1)
-        .unwrap();
+/// A depth-first search for the best constrained pick, see [`BankSolver::pick`]
+///
+/// Each slot tries digits from best to worst, taking the earliest battery with
+/// that digit since it leaves the most choice for later slots. The first
+/// complete pick found is therefore the best one. States that can't be
+/// completed are remembered so they're never explored twice.
+struct Search<'a> {
+    solver: &'a BankSolver,
+    bank: &'a [u32],
+    /// How many times each capped digit has been picked
+    used: [usize; 10],
+    picked: Vec<usize>,
+    failed: HashSet<(usize, usize, [usize; 10])>,
+}
 
-    acc.push(first.1);
+impl Search<'_> {
+    /// Try to pick `remaining` more batteries starting at index `start`
+    fn extend(&mut self, start: usize, remaining: usize) -> bool {
+        if remaining == 0 {
+            return true;
+        }
 
-    let remaining_pairs = pairs
-        .into_iter()
-        .filter(|(i, _)| i > &first.0)
-        .collect::<Vec<_>>();
+        // even picking every allowed battery wouldn't be enough
+        let stride = self.solver.gap() + 1;
+        let available = self.bank.len().saturating_sub(start).div_ceil(stride);
+        if available < remaining || self.failed.contains(&(start, remaining, self.used)) {
+            return false;
+        }
+
+        let goal = self.solver.goal;
+        for digit in (0..=9).map(|d| if goal == Goal::Maximize { 9 - d } else { d }) {
+            let cap = self.solver.caps[digit as usize];
+            if cap.is_some_and(|cap| self.used[digit as usize] >= cap) {
+                continue;
+            }
+            let Some(offset) = self.bank[start..].iter().position(|&d| d == digit) else {
+                continue;
+            };
+
+            let index = start + offset;
+            if cap.is_some() {
+                self.used[digit as usize] += 1;
+            }
+            self.picked.push(index);
+
+            if self.extend(index + stride, remaining - 1) {
+                return true;
+            }
 
-    largest_joltage_pt2_helper(remaining_pairs, length - 1, acc)
+            self.picked.pop();
+            if cap.is_some() {
+                self.used[digit as usize] -= 1;
+            }
+        }
+
+        self.failed.insert((start, remaining, self.used));
+        false
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
+
     use super::*;
+    use crate::util::lcg::Lcg;
 
     const TEST_INPUT: &str = "\
         987654321111111\n\
@@ -98,25 +298,25 @@ mod tests {
     #[test]
     fn test_largest_joltage_pt1() {
         let bank = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 0];
-        assert_eq!(largest_joltage_pt1(&bank), 90);
+        assert_eq!(BankSolver::new(2).joltage(&bank).unwrap(), BigUint::from(90u32));
 
         let bank = vec![1, 2, 3, 4, 5, 6, 7, 8, 0, 9];
-        assert_eq!(largest_joltage_pt1(&bank), 89);
+        assert_eq!(BankSolver::new(2).joltage(&bank).unwrap(), BigUint::from(89u32));
     }
 
     #[test]
     fn test_largest_joltage_pt2() {
         let bank = parse_input_line("987654321111111");
-        assert_eq!(largest_joltage_pt2(&bank, 12), 987654321111);
+        assert_eq!(BankSolver::new(12).joltage(&bank).unwrap(), BigUint::from(987654321111u64));
 
         let bank = parse_input_line("811111111111119");
-        assert_eq!(largest_joltage_pt2(&bank, 12), 811111111119);
+        assert_eq!(BankSolver::new(12).joltage(&bank).unwrap(), BigUint::from(811111111119u64));
 
         let bank = parse_input_line("234234234234278");
-        assert_eq!(largest_joltage_pt2(&bank, 12), 434234234278);
+        assert_eq!(BankSolver::new(12).joltage(&bank).unwrap(), BigUint::from(434234234278u64));
 
         let bank = parse_input_line("818181911112111");
-        assert_eq!(largest_joltage_pt2(&bank, 12), 888911112111);
+        assert_eq!(BankSolver::new(12).joltage(&bank).unwrap(), BigUint::from(888911112111u64));
     }
 
     #[test]
@@ -130,10 +330,127 @@ mod tests {
             })
             .collect::<Vec<_>>();
 
-        let total_joltage = bank
-            .iter()
-            .map(|line| largest_joltage_pt1(line))
-            .sum::<u32>();
-        assert_eq!(total_joltage, 357);
+        assert_eq!(BankSolver::new(2).total_joltage(&bank).unwrap(), BigUint::from(357u32));
+    }
+
+    #[test]
+    fn test_total_joltage_pt2() {
+        let bank = TEST_INPUT.lines().map(parse_input_line).collect::<Vec<_>>();
+        let total = BankSolver::new(12).total_joltage(&bank).unwrap();
+        assert_eq!(total, BigUint::from(3121910778619u64));
+    }
+
+    #[test]
+    fn test_largest_subsequence() {
+        let bank = parse_input_line("3729");
+        assert_eq!(digits(&BankSolver::new(0).select(&bank).unwrap(), &bank), vec![]);
+        assert_eq!(digits(&BankSolver::new(1).select(&bank).unwrap(), &bank), vec![9]);
+        assert_eq!(digits(&BankSolver::new(3).select(&bank).unwrap(), &bank), vec![7, 2, 9]);
+        assert_eq!(digits(&BankSolver::new(4).select(&bank).unwrap(), &bank), bank);
+
+        assert!(BankSolver::new(5).select(&bank).is_err());
+    }
+
+    #[test]
+    fn test_long_joltage() {
+        // far beyond what fits in a u128
+        let bank = [9; 100];
+        let joltage = BankSolver::new(60).joltage(&bank).unwrap();
+        assert_eq!(joltage.to_string(), "9".repeat(60));
+
+        let total = BankSolver::new(60).total_joltage(&[bank.to_vec(), bank.to_vec()]).unwrap();
+        assert_eq!(total, joltage * 2u32);
+    }
+
+    fn digits(selection: &Selection, bank: &[u32]) -> Vec<u32> {
+        selection.indices.iter().map(|&i| bank[i]).collect()
+    }
+
+    /// The best digits found by trying every combination of batteries
+    fn brute_force(solver: &BankSolver, bank: &[u32]) -> Option<Vec<u32>> {
+        let candidates = (0..bank.len())
+            .combinations(solver.length)
+            .filter(|picked| picked.windows(2).all(|w| w[1] - w[0] > solver.gap()))
+            .map(|picked| picked.iter().map(|&i| bank[i]).collect::<Vec<_>>())
+            .filter(|digits| {
+                (0..10).all(|digit| {
+                    solver.caps[digit].is_none_or(|cap| {
+                        digits.iter().filter(|&&d| d as usize == digit).count() <= cap
+                    })
+                })
+            });
+        match solver.goal {
+            Goal::Maximize => candidates.max(),
+            Goal::Minimize => candidates.min(),
+        }
+    }
+
+    #[test]
+    fn test_selection() {
+        let bank = parse_input_line("234234234234278");
+        let selection = BankSolver::new(12).select(&bank).unwrap();
+        assert_eq!(selection.indices, vec![2, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14]);
+        assert_eq!(selection.value, BigUint::from(434234234278u64));
+
+        let selection = BankSolver::new(2).select(&parse_input_line("8119")).unwrap();
+        assert_eq!(selection.indices, vec![0, 3]);
+        assert_eq!(
+            selection.render(&parse_input_line("8119")),
+            "\x1b[1;32m8\x1b[0m11\x1b[1;32m9\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_constrained_joltage() {
+        let bank = parse_input_line("818181911112111");
+
+        let solver = BankSolver {
+            goal: Goal::Minimize,
+            ..BankSolver::new(4)
+        };
+        assert_eq!(solver.joltage(&bank).unwrap(), BigUint::from(1111u32));
+
+        let solver = BankSolver {
+            no_adjacent: true,
+            ..BankSolver::new(4)
+        };
+        assert_eq!(digits(&solver.select(&bank).unwrap(), &bank), vec![9, 1, 2, 1]);
+
+        let mut solver = BankSolver::new(4);
+        solver.caps[9] = Some(0);
+        assert_eq!(digits(&solver.select(&bank).unwrap(), &bank), vec![8, 8, 8, 2]);
+        solver.caps[8] = Some(1);
+        assert_eq!(digits(&solver.select(&bank).unwrap(), &bank), vec![8, 2, 1, 1]);
+
+        let solver = BankSolver {
+            min_gap: 7,
+            ..BankSolver::new(3)
+        };
+        assert!(solver.joltage(&bank).is_err());
+    }
+
+    #[test]
+    fn test_constrained_joltage_matches_brute_force() {
+        let mut rng = Lcg::new(3);
+        let mut next = move |limit: u64| rng.next(limit);
+
+        for _ in 0..200 {
+            let bank = (0..next(10) + 4).map(|_| next(4) as u32 + 6).collect::<Vec<_>>();
+            let mut solver = BankSolver {
+                goal: if next(2) == 0 {
+                    Goal::Maximize
+                } else {
+                    Goal::Minimize
+                },
+                min_gap: next(3) as usize,
+                ..BankSolver::new(next(5) as usize + 1)
+            };
+            solver.caps[next(4) as usize + 6] = Some(next(3) as usize);
+
+            let expected = brute_force(&solver, &bank);
+            let selected = solver.select(&bank).ok();
+            let picked = selected.map(|selection| digits(&selection, &bank));
+            assert_eq!(picked, expected, "{bank:?} {solver:?}");
+        }
     }
 }
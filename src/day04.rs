@@ -1,71 +1,414 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use clap::{Parser, ValueEnum};
+
 use crate::util::{Coord, Grid};
 
-pub(crate) fn run(input: String) -> eyre::Result<()> {
-    let grid = parse_input(&input);
-    let accessible = accessible_stacks(&grid);
+#[derive(Parser)]
+struct Options {
+    /// A stack is accessible when its neighbors weigh less than this
+    #[arg(long, default_value_t = 4)]
+    threshold: u32,
+    /// Look at the 4 or 8 cells around each stack
+    #[arg(long, value_enum, default_value_t = Adjacency::Eight)]
+    neighbors: Adjacency,
+    /// Look at a custom set of cells instead, as `row,col` offsets split by `;`
+    #[arg(
+        long,
+        value_name = "OFFSETS",
+        conflicts_with = "neighbors",
+        value_parser = parse_stencil,
+        allow_hyphen_values = true
+    )]
+    stencil: Option<Neighborhood>,
+    /// Whether cells past the edge of the grid count as occupied
+    #[arg(long, value_enum, default_value_t = Edges::Empty)]
+    edges: Edges,
+    /// Play the removal rounds back in the terminal
+    #[arg(long)]
+    animate: bool,
+    /// Milliseconds to show each frame of the animation for
+    #[arg(long, default_value_t = 200, requires = "animate")]
+    delay: u64,
+    /// Write every frame of the removal to a text file in this directory
+    #[arg(long, value_name = "DIR")]
+    frames_dir: Option<PathBuf>,
+    /// Treat CHAR as a blocker that's never removed and weighs WEIGHT, e.g. `--blocker #=4`
+    #[arg(long, value_name = "CHAR=WEIGHT", value_parser = parse_blocker)]
+    blocker: Vec<(char, u32)>,
+    /// Report the round the stack at ROW,COL becomes accessible in
+    #[arg(long, value_name = "ROW,COL", value_parser = parse_coord::<usize>)]
+    when: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Adjacency {
+    #[value(name = "4")]
+    Four,
+    #[value(name = "8")]
+    Eight,
+}
+
+fn parse_coord<T: FromStr>(s: &str) -> Result<(T, T), String>
+where
+    T::Err: fmt::Display,
+{
+    let (row, col) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected ROW,COL, got {s:?}"))?;
+    let parse = |n: &str| n.trim().parse().map_err(|e| format!("invalid {n:?}: {e}"));
+    Ok((parse(row)?, parse(col)?))
+}
+
+fn parse_blocker(s: &str) -> Result<(char, u32), String> {
+    let (symbol, weight) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected CHAR=WEIGHT, got {s:?}"))?;
+    let mut chars = symbol.chars();
+    let (Some(symbol), None) = (chars.next(), chars.next()) else {
+        return Err(format!("{symbol:?} isn't a single character"));
+    };
+    if symbol == '@' || symbol == '.' || symbol.is_ascii_digit() {
+        return Err(format!("{symbol:?} is already used for stacks and empty cells"));
+    }
+    let weight = weight.parse().map_err(|e| format!("invalid weight: {e}"))?;
+    Ok((symbol, weight))
+}
+
+fn parse_stencil(s: &str) -> Result<Neighborhood, String> {
+    let offsets = s
+        .split(';')
+        .map(parse_coord::<isize>)
+        .collect::<Result<Vec<_>, _>>()?;
+    if offsets.contains(&(0, 0)) {
+        return Err("a stack can't be its own neighbor".to_string());
+    }
+    Ok(Neighborhood::Custom(offsets))
+}
+
+impl Options {
+    fn rule(&self) -> AccessRule {
+        let neighborhood = match (&self.stencil, self.neighbors) {
+            (Some(stencil), _) => stencil.clone(),
+            (None, Adjacency::Four) => Neighborhood::Four,
+            (None, Adjacency::Eight) => Neighborhood::Eight,
+        };
+        AccessRule {
+            threshold: self.threshold,
+            neighborhood,
+            edges: self.edges,
+        }
+    }
+}
+
+pub(crate) fn run(input: String, args: &[String]) -> eyre::Result<()> {
+    let options: Options = crate::parse_day_options("04", args);
+    let rule = options.rule();
+    let grid = parse_input(&input, &options.blocker);
+    let accessible = accessible_stacks(&grid, &rule);
     println!("Part 1: {accessible}");
 
-    let accessible = accessible_stacks_after_removal(&grid);
-    println!("Part 2: {accessible}");
+    let erosion = Erosion::new(&grid, &rule);
+
+    if let Some(dir) = &options.frames_dir {
+        std::fs::create_dir_all(dir)?;
+        for (round, frame) in erosion.frames().enumerate() {
+            std::fs::write(dir.join(format!("frame_{round:03}.txt")), frame)?;
+        }
+    }
+
+    if options.animate {
+        for (round, frame) in erosion.frames().enumerate() {
+            // clear the screen and draw from the top left
+            print!("\x1b[2J\x1b[H{frame}");
+            match erosion.rounds().get(round) {
+                Some(removed) => println!("Round {}: removing {}", round + 1, removed.len()),
+                None => println!("Nothing left to remove"),
+            }
+            thread::sleep(Duration::from_millis(options.delay));
+        }
+    }
+
+    println!("Part 2: {}", erosion.total_removed());
+
+    if let Some((row, col)) = options.when {
+        match (grid.get((row, col)), erosion.round_of((row, col))) {
+            (Some(Cell::Stack(_)), Some(round)) => {
+                println!("The stack at {row},{col} is removed in round {}", round + 1)
+            }
+            (Some(Cell::Stack(_)), None) => {
+                println!("The stack at {row},{col} is never removed")
+            }
+            _ => println!("There's no stack at {row},{col}"),
+        }
+    }
 
     Ok(())
 }
 
-fn parse_input(input: &str) -> Grid<()> {
+/// Something taking up a cell of the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    /// A stack that can be removed, with how much it weighs on its neighbors
+    Stack(u32),
+    /// Something that's never removed, with how much it weighs on its neighbors
+    Blocker(u32),
+}
+
+impl Cell {
+    fn weight(&self) -> u32 {
+        match self {
+            Cell::Stack(weight) | Cell::Blocker(weight) => *weight,
+        }
+    }
+}
+
+/// Parse the map, where `@` is a stack weighing 1 and a digit is a stack of that weight
+///
+/// Any of the `blockers` symbols becomes a blocker of the given weight, and
+/// anything else is empty.
+fn parse_input(input: &str, blockers: &[(char, u32)]) -> Grid<Cell> {
     let mut grid = Grid::new(input.lines().next().unwrap().len(), input.lines().count());
     for (row, line) in input.lines().enumerate() {
         for (col, c) in line.chars().enumerate() {
-            if c == '@' {
-                grid.set((row, col), ());
-            }
+            let cell = match c {
+                '@' => Cell::Stack(1),
+                '0'..='9' => Cell::Stack(c.to_digit(10).unwrap()),
+                _ => match blockers.iter().find(|(symbol, _)| *symbol == c) {
+                    Some(&(_, weight)) => Cell::Blocker(weight),
+                    None => continue,
+                },
+            };
+            grid.set((row, col), cell);
         }
     }
     grid
 }
 
-fn accessible_stacks(grid: &Grid<()>) -> usize {
-    let mut accessible = 0;
-    for (coord, _) in grid.iter_filled() {
-        let neighbors = grid.neighbors8(coord).collect::<Vec<_>>();
-        if neighbors.iter().filter(|n| grid.get(**n).is_some()).count() < 4 {
-            accessible += 1;
+/// Which cells around a stack count as its neighbors
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Neighborhood {
+    /// The cells directly above, below, left and right
+    Four,
+    /// The four cells plus the diagonals
+    Eight,
+    /// Any `(row, col)` offsets
+    Custom(Vec<(isize, isize)>),
+}
+
+impl Neighborhood {
+    const FOUR: [(isize, isize); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+    const EIGHT: [(isize, isize); 8] = [
+        (-1, -1),
+        (-1, 0),
+        (-1, 1),
+        (0, 1),
+        (1, 1),
+        (1, 0),
+        (1, -1),
+        (0, -1),
+    ];
+
+    fn offsets(&self) -> &[(isize, isize)] {
+        match self {
+            Neighborhood::Four => &Self::FOUR,
+            Neighborhood::Eight => &Self::EIGHT,
+            Neighborhood::Custom(offsets) => offsets,
         }
     }
-    accessible
 }
 
-fn accessible_stacks_after_removal(grid: &Grid<()>) -> usize {
-    let mut total_accessible = 0;
-    let mut grid = grid.clone();
+/// How cells past the edge of the grid are treated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum Edges {
+    #[default]
+    Empty,
+    /// Every cell past the edge weighs 1
+    Occupied,
+}
 
-    let mut last: Option<Vec<Coord>> = None;
-    while last.as_ref().is_none() || !last.as_ref().unwrap().is_empty() {
-        if let Some(last) = last {
-            for coord in last {
-                grid.clear(coord);
+/// Decides whether a stack can be reached by a forklift
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AccessRule {
+    /// A stack is accessible when its neighbors weigh less than this in total
+    threshold: u32,
+    neighborhood: Neighborhood,
+    edges: Edges,
+}
+
+impl Default for AccessRule {
+    fn default() -> Self {
+        Self {
+            threshold: 4,
+            neighborhood: Neighborhood::Eight,
+            edges: Edges::Empty,
+        }
+    }
+}
+
+impl AccessRule {
+    /// The total weight of the cells around `coord`
+    fn neighbor_weight(&self, grid: &Grid<Cell>, coord: Coord) -> u32 {
+        self.neighborhood
+            .offsets()
+            .iter()
+            .map(|&(row, col)| {
+                let neighbor = Coord::new(coord.row + row, coord.col + col);
+                if grid.in_bounds(neighbor) {
+                    grid.get(neighbor).map_or(0, Cell::weight)
+                } else {
+                    (self.edges == Edges::Occupied) as u32
+                }
+            })
+            .sum()
+    }
+
+    fn is_accessible(&self, grid: &Grid<Cell>, coord: Coord) -> bool {
+        matches!(grid.get(coord), Some(Cell::Stack(_)))
+            && self.neighbor_weight(grid, coord) < self.threshold
+    }
+
+    /// The cells that have `coord` as one of their neighbors
+    ///
+    /// Custom stencils don't have to be symmetric, so this isn't always the
+    /// same as the neighbors of `coord`.
+    fn dependents<'a>(
+        &'a self,
+        grid: &'a Grid<Cell>,
+        coord: Coord,
+    ) -> impl Iterator<Item = Coord> + 'a {
+        self.neighborhood
+            .offsets()
+            .iter()
+            .map(move |&(row, col)| Coord::new(coord.row - row, coord.col - col))
+            .filter(|dependent| grid.in_bounds(*dependent))
+    }
+}
+
+fn accessible_stacks(grid: &Grid<Cell>, rule: &AccessRule) -> usize {
+    grid.iter_filled()
+        .filter(|(coord, _)| rule.is_accessible(grid, *coord))
+        .count()
+}
+
+/// The history of removing accessible stacks, round by round
+#[derive(Debug, Clone)]
+struct Erosion {
+    /// The stacks removed in each round, in order
+    rounds: Vec<Vec<Coord>>,
+    /// What happens to every filled cell
+    fates: Grid<Fate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fate {
+    /// A stack that became accessible in the given round
+    Removed(usize),
+    /// A stack that's never accessible
+    Stays,
+    Blocker,
+}
+
+impl Erosion {
+    /// Run the removal to completion
+    ///
+    /// Rather than rescanning the grid each round, this keeps the weight around
+    /// every stack. Removing a stack only touches the weights of the stacks next
+    /// to it, and a stack is queued for the next round the moment its weight
+    /// drops below the threshold.
+    fn new(grid: &Grid<Cell>, rule: &AccessRule) -> Self {
+        let mut weights = Grid::new(grid.width(), grid.height());
+        let mut fates = Grid::new(grid.width(), grid.height());
+        for (coord, cell) in grid.iter_filled() {
+            match cell {
+                Cell::Stack(_) => {
+                    weights.set(coord, rule.neighbor_weight(grid, coord));
+                    fates.set(coord, Fate::Stays);
+                }
+                Cell::Blocker(_) => {
+                    fates.set(coord, Fate::Blocker);
+                }
             }
         }
 
-        last = Some(Vec::new());
-        for (coord, _) in grid.iter_filled() {
-            let neighbors_count = grid
-                .neighbors8(coord)
-                .filter(|n| grid.get(*n).is_some())
-                .count();
+        let mut round = weights
+            .iter_filled()
+            .filter(|(_, weight)| **weight < rule.threshold)
+            .map(|(coord, _)| coord)
+            .collect::<Vec<_>>();
 
-            if neighbors_count < 4 {
-                total_accessible += 1;
-                if let Some(l) = last.as_mut() { l.push(coord) }
+        let mut rounds = Vec::new();
+        while !round.is_empty() {
+            for &coord in &round {
+                weights.clear(coord);
+                fates.set(coord, Fate::Removed(rounds.len()));
             }
+
+            let mut next_round = Vec::new();
+            for &coord in &round {
+                let removed = grid.get(coord).map_or(0, Cell::weight);
+                for dependent in rule.dependents(grid, coord) {
+                    if let Some(weight) = weights.get_mut(dependent) {
+                        // weights only go down, so each stack crosses the threshold once
+                        let before = *weight;
+                        *weight -= removed;
+                        if before >= rule.threshold && *weight < rule.threshold {
+                            next_round.push(dependent);
+                        }
+                    }
+                }
+            }
+            rounds.push(round);
+            round = next_round;
+        }
+
+        Self { rounds, fates }
+    }
+
+    /// Get a reference to the stacks removed in each round
+    fn rounds(&self) -> &[Vec<Coord>] {
+        &self.rounds
+    }
+
+    fn total_removed(&self) -> usize {
+        self.rounds.iter().map(Vec::len).sum()
+    }
+
+    /// The round (counting from 0) in which the stack at `coord` became accessible
+    fn round_of(&self, coord: impl Into<Coord>) -> Option<usize> {
+        match self.fates.get(coord) {
+            Some(Fate::Removed(round)) => Some(*round),
+            _ => None,
         }
     }
 
-    total_accessible
+    /// Draw the grid at the start of `round`, marking the stacks about to go with `x`
+    fn frame(&self, round: usize) -> String {
+        self.fates.render(|fate| match fate {
+            None => ".",
+            Some(Fate::Removed(removed)) if *removed < round => ".",
+            Some(Fate::Removed(removed)) if *removed == round => "x",
+            Some(Fate::Removed(_) | Fate::Stays) => "@",
+            Some(Fate::Blocker) => "#",
+        })
+    }
+
+    /// Every frame of the removal, ending with the stacks that are left at the end
+    fn frames(&self) -> impl Iterator<Item = String> + '_ {
+        (0..=self.rounds.len()).map(|round| self.frame(round))
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use indoc::indoc;
+
     use super::*;
+    use crate::util::lcg::Lcg;
 
     const TEST_INPUT: &str = "\
     ..@@.@@@@.\n\
@@ -81,22 +424,198 @@ mod tests {
 
     #[test]
     fn test_parse_input() {
-        let grid = parse_input(TEST_INPUT);
+        let grid = parse_input(TEST_INPUT, &[]);
         assert_eq!(grid.width(), 10);
         assert_eq!(grid.height(), 10);
         assert_eq!(grid.get((0, 0)), None);
-        assert_eq!(grid.get((0, 3)), Some(&()));
+        assert_eq!(grid.get((0, 3)), Some(&Cell::Stack(1)));
     }
 
     #[test]
     fn test_accessible_stacks() {
-        let grid = parse_input(TEST_INPUT);
-        assert_eq!(accessible_stacks(&grid), 13);
+        let grid = parse_input(TEST_INPUT, &[]);
+        assert_eq!(accessible_stacks(&grid, &AccessRule::default()), 13);
     }
 
     #[test]
     fn test_accessible_stacks_after_removal() {
-        let grid = parse_input(TEST_INPUT);
-        assert_eq!(accessible_stacks_after_removal(&grid), 43);
+        let grid = parse_input(TEST_INPUT, &[]);
+        assert_eq!(Erosion::new(&grid, &AccessRule::default()).total_removed(), 43);
+    }
+
+    /// Remove stacks round by round, rescanning the whole grid each time
+    fn rescan_after_removal(grid: &Grid<Cell>, rule: &AccessRule) -> usize {
+        let mut grid = grid.clone();
+        let mut total = 0;
+        loop {
+            let accessible = grid
+                .iter_filled()
+                .map(|(coord, _)| coord)
+                .filter(|&coord| rule.is_accessible(&grid, coord))
+                .collect::<Vec<_>>();
+            if accessible.is_empty() {
+                return total;
+            }
+            total += accessible.len();
+            for coord in accessible {
+                grid.clear(coord);
+            }
+        }
+    }
+
+    #[test]
+    fn test_removal_matches_rescan() {
+        let rules = [
+            AccessRule::default(),
+            AccessRule {
+                threshold: 2,
+                neighborhood: Neighborhood::Four,
+                ..AccessRule::default()
+            },
+            AccessRule {
+                edges: Edges::Occupied,
+                ..AccessRule::default()
+            },
+            AccessRule {
+                threshold: 3,
+                neighborhood: Neighborhood::Custom(vec![(0, 1), (0, 2), (1, 0), (-2, -1)]),
+                edges: Edges::Occupied,
+            },
+        ];
+
+        let mut rng = Lcg::new(11);
+        for _ in 0..20 {
+            let mut grid = Grid::new(40, 25);
+            for coord in grid.coords().collect::<Vec<_>>() {
+                // dense enough that most stacks start out blocked
+                let cell = match rng.next(20) {
+                    0..=11 => Cell::Stack(1),
+                    12..=14 => Cell::Stack(2),
+                    15 => Cell::Stack(0),
+                    16 => Cell::Blocker(1),
+                    17 => Cell::Blocker(3),
+                    _ => continue,
+                };
+                grid.set(coord, cell);
+            }
+            for rule in &rules {
+                assert_eq!(
+                    Erosion::new(&grid, rule).total_removed(),
+                    rescan_after_removal(&grid, rule),
+                    "{rule:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_erosion() {
+        let grid = parse_input(TEST_INPUT, &[]);
+        let erosion = Erosion::new(&grid, &AccessRule::default());
+
+        let removed = erosion.rounds().iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(removed, vec![13, 12, 7, 5, 2, 1, 1, 1, 1]);
+        assert_eq!(erosion.total_removed(), 43);
+
+        assert_eq!(erosion.round_of((0, 2)), Some(0));
+        assert_eq!(erosion.round_of((0, 7)), Some(1));
+        assert_eq!(erosion.round_of((4, 4)), None);
+        assert_eq!(erosion.round_of((0, 0)), None);
+
+        let first = indoc! {"
+            ..xx.xx@x.
+            x@@.@.@.@@
+            @@@@@.x.@@
+            @.@@@@..@.
+            x@.@@@@.@x
+            .@@@@@@@.@
+            .@.@.@.@@@
+            x.@@@.@@@@
+            .@@@@@@@@.
+            x.x.@@@.x.
+        "};
+        assert_eq!(erosion.frame(0), first);
+
+        let frames = erosion.frames().collect::<Vec<_>>();
+        assert_eq!(frames.len(), 10);
+        assert!(!frames[9].contains('x'));
+        assert_eq!(frames[9].matches('@').count(), grid.iter_filled().count() - 43);
+    }
+
+    #[test]
+    fn test_access_rule() {
+        let grid = parse_input("@@@\n@@@", &[]);
+        let rule = AccessRule::default();
+        assert_eq!(rule.neighbor_weight(&grid, Coord::new(0, 1)), 5);
+        assert_eq!(accessible_stacks(&grid, &rule), 4);
+
+        let rule = AccessRule {
+            edges: Edges::Occupied,
+            ..AccessRule::default()
+        };
+        assert_eq!(rule.neighbor_weight(&grid, Coord::new(0, 0)), 8);
+        assert_eq!(accessible_stacks(&grid, &rule), 0);
+
+        let rule = AccessRule {
+            threshold: 3,
+            neighborhood: Neighborhood::Four,
+            ..AccessRule::default()
+        };
+        assert_eq!(accessible_stacks(&grid, &rule), 4);
+        assert_eq!(Erosion::new(&grid, &rule).total_removed(), 6);
+
+        // only the cell to the right matters, so stacks go from the right edge inwards
+        let rule = AccessRule {
+            threshold: 1,
+            neighborhood: Neighborhood::Custom(vec![(0, 1)]),
+            ..AccessRule::default()
+        };
+        let erosion = Erosion::new(&grid, &rule);
+        assert_eq!(erosion.rounds().len(), 3);
+        assert_eq!(erosion.round_of((1, 2)), Some(0));
+        assert_eq!(erosion.round_of((1, 0)), Some(2));
+
+        assert_eq!(
+            parse_stencil("-1,0; 0,2").unwrap(),
+            Neighborhood::Custom(vec![(-1, 0), (0, 2)])
+        );
+        assert!(parse_stencil("0,0").is_err());
+
+        // offsets starting with a minus sign aren't mistaken for flags
+        let args = ["advent 04 --", "--stencil", "-1,0;1,0;0,2"];
+        let options = Options::try_parse_from(args).unwrap();
+        assert_eq!(
+            options.stencil,
+            Some(Neighborhood::Custom(vec![(-1, 0), (1, 0), (0, 2)]))
+        );
+    }
+
+    #[test]
+    fn test_weighted_cells() {
+        let grid = parse_input("#@3\n.9@", &[('#', 2)]);
+        assert_eq!(grid.get((0, 0)), Some(&Cell::Blocker(2)));
+        assert_eq!(grid.get((0, 2)), Some(&Cell::Stack(3)));
+        assert_eq!(grid.get((1, 0)), None);
+
+        let rule = AccessRule::default();
+        assert_eq!(rule.neighbor_weight(&grid, Coord::new(0, 1)), 15);
+        assert_eq!(rule.neighbor_weight(&grid, Coord::new(1, 2)), 13);
+
+        let rule = AccessRule {
+            threshold: 8,
+            ..AccessRule::default()
+        };
+        // only the heavy stack has light enough neighbors, and taking it frees the rest
+        let erosion = Erosion::new(&grid, &rule);
+        assert_eq!(erosion.round_of((1, 1)), Some(0));
+        assert_eq!(erosion.round_of((0, 1)), Some(1));
+        assert_eq!(erosion.round_of((0, 2)), Some(1));
+        assert_eq!(erosion.round_of((1, 2)), Some(1));
+        assert_eq!(erosion.round_of((0, 0)), None);
+        assert_eq!(erosion.frame(1), "#xx\n..x\n");
+
+        assert_eq!(parse_blocker("#=4").unwrap(), ('#', 4));
+        assert!(parse_blocker("@=4").is_err());
+        assert!(parse_blocker("ab=4").is_err());
     }
 }
@@ -1,8 +1,6 @@
-use std::ops::RangeInclusive;
+use crate::util::intervals::{Interval, IntervalSet};
 
-use range_set::range_set;
-
-pub(crate) fn run(input: String) -> eyre::Result<()> {
+pub(crate) fn run(input: String, _args: &[String]) -> eyre::Result<()> {
     let kitchen = parse_kitchen(&input);
 
     let mut total_fresh = 0;
@@ -20,50 +18,43 @@ pub(crate) fn run(input: String) -> eyre::Result<()> {
 }
 
 struct Kitchen {
-    fresh_ranges: Vec<RangeInclusive<u128>>,
+    /// The fresh ranges, merged so they're sorted and disjoint
+    fresh_ranges: IntervalSet<u128>,
     ingredients: Vec<u128>,
 }
 
 impl Kitchen {
-    fn new(ranges: Vec<RangeInclusive<u128>>, ingredients: Vec<u128>) -> Self {
+    fn new(ranges: Vec<Interval<u128>>, ingredients: Vec<u128>) -> Self {
         Self {
-            fresh_ranges: ranges,
+            fresh_ranges: ranges.into_iter().collect(),
             ingredients,
         }
     }
 
+    /// Binary search for the only range that could hold the ingredient
     fn is_fresh(&self, ingredient: u128) -> bool {
-        self.fresh_ranges
-            .iter()
-            .any(|range| range.contains(&ingredient))
+        self.fresh_ranges.contains(ingredient)
     }
 
     fn total_fresh_ids(&self) -> u128 {
-        let mut set = range_set![];
-        for range in &self.fresh_ranges {
-            set.insert_range(range.clone());
-        }
-
-        set.len() as u128
+        self.fresh_ranges.len()
     }
 }
 
 fn parse_kitchen(input: &str) -> Kitchen {
     let (fresh_ranges, ingredients) = input.split_once("\n\n").unwrap();
-    let fresh_ranges = fresh_ranges
-        .lines()
-        .map(|line| {
-            let (start, end) = line.split_once('-').unwrap();
-            let start = start.parse::<u128>().unwrap();
-            let end = end.parse::<u128>().unwrap();
-            start..=end
-        })
-        .collect::<Vec<_>>();
     let ingredients = ingredients
         .lines()
         .map(|line| line.parse::<u128>().unwrap())
         .collect();
-    Kitchen::new(fresh_ranges, ingredients)
+    Kitchen::new(parse_ranges(fresh_ranges), ingredients)
+}
+
+fn parse_ranges(input: &str) -> Vec<Interval<u128>> {
+    input
+        .lines()
+        .map(|line| line.parse::<Interval<u128>>().unwrap())
+        .collect()
 }
 
 #[cfg(test)]
@@ -99,4 +90,78 @@ mod tests {
         let kitchen = parse_kitchen(TEST_INPUT);
         assert_eq!(kitchen.total_fresh_ids(), 14);
     }
+
+    /// The original check, looking through every range for each ingredient
+    fn is_fresh_linear(ranges: &[Interval<u128>], ingredient: u128) -> bool {
+        ranges.iter().any(|range| range.contains(ingredient))
+    }
+
+    #[test]
+    fn test_is_fresh_matches_linear() {
+        let kitchen = parse_kitchen(TEST_INPUT);
+        let ranges = parse_ranges(TEST_INPUT.split_once("\n\n").unwrap().0);
+        assert_eq!(kitchen.fresh_ranges.intervals().len(), 2);
+
+        for ingredient in 0..25 {
+            assert_eq!(
+                kitchen.is_fresh(ingredient),
+                is_fresh_linear(&ranges, ingredient),
+                "{ingredient}"
+            );
+        }
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture bench_is_fresh`
+    #[test]
+    #[ignore = "benchmark, needs inputs/05.txt"]
+    fn bench_is_fresh() {
+        use std::time::Instant;
+
+        let input = std::fs::read_to_string("inputs/05.txt").unwrap();
+        let kitchen = parse_kitchen(&input);
+        let ranges = parse_ranges(input.split_once("\n\n").unwrap().0);
+
+        // spread the queries over every range so neither side gets lucky
+        let queries = ranges
+            .iter()
+            .flat_map(|range| {
+                let (start, end) = (range.start(), range.end());
+                [start.saturating_sub(1), start, end, end.saturating_add(1)]
+            })
+            .chain(kitchen.ingredients.iter().copied())
+            .collect::<Vec<_>>();
+        let rounds = 20;
+
+        let start = Instant::now();
+        let mut linear = 0;
+        for _ in 0..rounds {
+            linear += queries
+                .iter()
+                .filter(|&&q| is_fresh_linear(&ranges, std::hint::black_box(q)))
+                .count();
+        }
+        let linear_time = start.elapsed();
+
+        let start = Instant::now();
+        let mut merged = 0;
+        for _ in 0..rounds {
+            merged += queries
+                .iter()
+                .filter(|&&q| kitchen.is_fresh(std::hint::black_box(q)))
+                .count();
+        }
+        let merged_time = start.elapsed();
+
+        assert_eq!(linear, merged);
+        println!(
+            "{} queries against {} ranges ({} merged)",
+            queries.len() * rounds,
+            ranges.len(),
+            kitchen.fresh_ranges.intervals().len(),
+        );
+        println!(
+            "linear {linear_time:?}, binary search {merged_time:?}, {:.1}x faster",
+            linear_time.as_secs_f64() / merged_time.as_secs_f64()
+        );
+    }
 }
@@ -1,6 +1,6 @@
 use std::str::FromStr;
 
-pub(crate) fn run(input: String) -> eyre::Result<()> {
+pub(crate) fn run(input: String, _args: &[String]) -> eyre::Result<()> {
     let worksheet: Worksheet = input.parse().unwrap();
     let answers = worksheet.answers();
     let sum = answers.iter().sum::<i128>();
@@ -193,7 +193,7 @@ mod tests {
     use super::*;
 
     fn test_input() -> String {
-        vec![
+        [
             "123 328  51 64 ",
             " 45 64  387 23 ",
             "  6 98  215 314",
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::util::{Coord, Direction4, Grid};
 
-pub(crate) fn run(input: String) -> eyre::Result<()> {
+pub(crate) fn run(input: String, _args: &[String]) -> eyre::Result<()> {
     let mut manifold = Manifold::from_input(&input);
     manifold.run();
     manifold.print();
@@ -1,67 +1,71 @@
-use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::path::PathBuf;
 
-use itertools::Itertools;
+use clap::{Parser, ValueEnum};
 
-pub(crate) fn run(input: String) -> eyre::Result<()> {
-    let mut playground = parse_input(&input);
+use crate::util::dsu::DisjointSet;
+use crate::util::kdtree::{KdTree, Metric};
+use crate::util::mst::{Edge, SpanningTree, kruskal};
+
+#[derive(Parser)]
+struct Options {
+    /// How the distance between two junction boxes is measured
+    #[arg(long, value_enum, default_value_t)]
+    metric: Metric,
+    /// Export the circuits formed for part 1
+    #[arg(long, value_enum)]
+    export: Option<ExportFormat>,
+    /// Write the export to a file instead of stdout
+    #[arg(long, requires = "export")]
+    output: Option<PathBuf>,
+    /// Print the size and length of the spanning tree used for part 2
+    #[arg(long)]
+    tree: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Dot,
+    Json,
+}
+
+pub(crate) fn run(input: String, args: &[String]) -> eyre::Result<()> {
+    let options: Options = crate::parse_day_options("08", args);
+    let mut playground = parse_input(&input, options.metric);
     playground.make_connections(1000);
     let result: usize = playground
         .disjoint_set
-        .get_sets()
+        .set_sizes()
         .into_iter()
         .take(3)
-        .map(|set| set.len())
         .product();
     println!("Part 1: {}", result);
 
-    let last_pair = playground.connect_until_single_set();
-    let result = last_pair.unwrap().0.x * last_pair.unwrap().1.x;
-    println!("Part 2: {}", result);
-
-    Ok(())
-}
-
-#[derive(Debug, Clone)]
-struct DisjointSet {
-    parent: Vec<usize>,
-}
-
-impl DisjointSet {
-    fn new(size: usize) -> Self {
-        Self {
-            parent: (0..size).collect(),
+    if let Some(format) = options.export {
+        let export = match format {
+            ExportFormat::Dot => playground.export_dot(),
+            ExportFormat::Json => playground.export_json(),
+        };
+        match &options.output {
+            Some(path) => std::fs::write(path, export)?,
+            None => print!("{export}"),
         }
     }
 
-    fn find(&mut self, item: usize) -> usize {
-        if self.parent[item] != item {
-            // compress the path as we walk it
-            self.parent[item] = self.find(self.parent[item]);
-        }
-
-        self.parent[item]
+    let tree = playground.minimum_spanning_tree();
+    if options.tree {
+        println!(
+            "Spanning tree: {} wires, {:.2} total length",
+            tree.edges().len(),
+            playground.wire_length(tree.edges())
+        );
     }
 
-    fn unite(&mut self, item1: usize, item2: usize) {
-        let root1 = self.find(item1);
-        let root2 = self.find(item2);
-
-        if root1 != root2 {
-            self.parent[root2] = root1;
-        }
-    }
+    let last_pair = playground.last_connection(&tree);
+    let result = last_pair.unwrap().0.x * last_pair.unwrap().1.x;
+    println!("Part 2: {}", result);
 
-    fn get_sets(&mut self) -> Vec<Vec<usize>> {
-        let mut sets = BTreeMap::new();
-        for i in 0..self.parent.len() {
-            let root = self.find(i);
-            sets.entry(root).or_insert(vec![]).push(i);
-        }
-        sets.values()
-            .cloned()
-            .sorted_by(|a, b| b.len().cmp(&a.len()))
-            .collect::<Vec<_>>()
-    }
+    Ok(())
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -72,12 +76,8 @@ struct Vec3 {
 }
 
 impl Vec3 {
-    fn distance(&self, other: &Self) -> f64 {
-        let base = ((self.x - other.x).pow(2)
-            + (self.y - other.y).pow(2)
-            + (self.z - other.z).pow(2)) as f64;
-
-        base.sqrt()
+    fn as_array(&self) -> [i64; 3] {
+        [self.x, self.y, self.z]
     }
 }
 
@@ -85,50 +85,214 @@ impl Vec3 {
 struct Playground {
     junction_boxes: Vec<Vec3>,
     disjoint_set: DisjointSet,
-    distances: VecDeque<(f64, (usize, usize))>,
+    index: KdTree<3>,
+    /// Connections made by `make_connections` that joined two circuits
+    connections: Vec<Edge<i128>>,
 }
 
 impl Playground {
-    fn new(junction_boxes: Vec<Vec3>) -> Self {
+    fn new(junction_boxes: Vec<Vec3>, metric: Metric) -> Self {
         let len = junction_boxes.len();
-        let distances = junction_boxes
-            .iter()
-            .enumerate()
-            .combinations(2)
-            .map(|pair| {
-                let (i1, v1) = pair[0];
-                let (i2, v2) = pair[1];
-                (v1.distance(v2), (i1, i2))
-            })
-            .sorted_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
-            .collect();
+        let points = junction_boxes.iter().map(Vec3::as_array).collect();
+        let index = KdTree::with_metric(points, metric);
 
         Self {
             junction_boxes,
             disjoint_set: DisjointSet::new(len),
-            distances,
+            index,
+            connections: Vec::new(),
         }
     }
 
+    /// Lazily yield every pair of boxes, closest first
+    ///
+    /// Distances are exact (squared for Euclidean), and equal distances are
+    /// ordered by the boxes' indices.
+    fn distances(&self) -> impl Iterator<Item = (i128, (usize, usize))> + '_ {
+        self.index
+            .closest_pairs()
+            .map(|(distance, i1, i2)| (distance, (i1, i2)))
+    }
+
     fn make_connections(&mut self, count: usize) {
-        for (_, (i1, i2)) in self.distances.iter().take(count) {
-            self.disjoint_set.unite(*i1, *i2);
+        let pairs = self.distances().take(count).collect::<Vec<_>>();
+        for (distance, (i1, i2)) in pairs {
+            if self.disjoint_set.unite(i1, i2) {
+                self.connections.push(Edge::new(distance, i1, i2));
+            }
+        }
+    }
+
+    fn minimum_spanning_tree(&self) -> SpanningTree<i128> {
+        let edges = self
+            .distances()
+            .map(|(distance, (i1, i2))| Edge::new(distance, i1, i2));
+        kruskal(self.junction_boxes.len(), edges)
+    }
+
+    /// The total real length of wire needed for the given connections
+    fn wire_length(&self, edges: &[Edge<i128>]) -> f64 {
+        let metric = self.index.metric();
+        edges.iter().map(|edge| metric.length(edge.weight)).sum()
+    }
+
+    /// The pair of boxes whose connection finally joins every circuit together
+    fn last_connection(&self, tree: &SpanningTree<i128>) -> Option<(Vec3, Vec3)> {
+        tree.last_edge()
+            .map(|edge| (self.junction_boxes[edge.a], self.junction_boxes[edge.b]))
+    }
+}
+
+impl Playground {
+    /// Summarize every circuit formed so far, largest first
+    fn circuits(&mut self) -> Vec<Circuit> {
+        let sets = self.disjoint_set.sets();
+        let mut circuit_of = vec![0; self.junction_boxes.len()];
+        for (i, set) in sets.iter().enumerate() {
+            for &member in set {
+                circuit_of[member] = i;
+            }
+        }
+
+        let mut circuits = sets
+            .into_iter()
+            .map(|members| Circuit::new(members, &self.junction_boxes))
+            .collect::<Vec<_>>();
+
+        for edge in &self.connections {
+            circuits[circuit_of[edge.a]].connections.push(*edge);
+        }
+        for circuit in circuits.iter_mut() {
+            circuit.wire_length = self.wire_length(&circuit.connections);
         }
+
+        circuits
+    }
+
+    /// Render the circuits as a Graphviz graph, one cluster per circuit
+    fn export_dot(&mut self) -> String {
+        let metric = self.index.metric();
+        let mut dot = String::from("graph circuits {\n");
+        for (i, circuit) in self.circuits().iter().enumerate() {
+            writeln!(dot, "  subgraph cluster_{i} {{").unwrap();
+            writeln!(
+                dot,
+                "    label=\"circuit {i}: {} boxes, {:.2} wire\";",
+                circuit.size(),
+                circuit.wire_length
+            )
+            .unwrap();
+            for &member in &circuit.members {
+                let Vec3 { x, y, z } = self.junction_boxes[member];
+                writeln!(dot, "    {member} [label=\"{x},{y},{z}\"];").unwrap();
+            }
+            for edge in &circuit.connections {
+                let length = metric.length(edge.weight);
+                writeln!(dot, "    {} -- {} [label=\"{length:.2}\"];", edge.a, edge.b).unwrap();
+            }
+            writeln!(dot, "  }}").unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render the circuits as a JSON document
+    fn export_json(&mut self) -> String {
+        let metric = self.index.metric();
+        let point = |v: &Vec3| format!("[{},{},{}]", v.x, v.y, v.z);
+
+        let circuits = self
+            .circuits()
+            .iter()
+            .map(|circuit| {
+                let boxes = circuit.boxes.iter().map(point).collect::<Vec<_>>();
+                let connections = circuit
+                    .connections
+                    .iter()
+                    .map(|edge| {
+                        format!(
+                            "{{\"a\":{},\"b\":{},\"length\":{}}}",
+                            edge.a,
+                            edge.b,
+                            metric.length(edge.weight)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let (cx, cy, cz) = circuit.centroid;
+                format!(
+                    "{{\"size\":{},\"members\":{:?},\"boxes\":[{}],\"centroid\":[{cx},{cy},{cz}],\
+                     \"bounding_box\":{{\"min\":{},\"max\":{}}},\"wire_length\":{},\
+                     \"connections\":[{}]}}",
+                    circuit.size(),
+                    circuit.members,
+                    boxes.join(","),
+                    point(&circuit.min),
+                    point(&circuit.max),
+                    circuit.wire_length,
+                    connections.join(",")
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!("{{\"circuits\":[{}]}}\n", circuits.join(","))
     }
+}
+
+/// A summary of one connected circuit of junction boxes
+#[derive(Debug, Clone)]
+struct Circuit {
+    members: Vec<usize>,
+    boxes: Vec<Vec3>,
+    centroid: (f64, f64, f64),
+    min: Vec3,
+    max: Vec3,
+    connections: Vec<Edge<i128>>,
+    wire_length: f64,
+}
+
+impl Circuit {
+    fn new(members: Vec<usize>, junction_boxes: &[Vec3]) -> Self {
+        let boxes = members
+            .iter()
+            .map(|&i| junction_boxes[i])
+            .collect::<Vec<_>>();
 
-    fn connect_until_single_set(&mut self) -> Option<(Vec3, Vec3)> {
-        let mut last_pair: Option<(usize, usize)> = None;
-        while self.disjoint_set.get_sets().len() > 1 {
-            let (_, (i1, i2)) = self.distances.pop_front().unwrap();
-            self.disjoint_set.unite(i1, i2);
-            last_pair = Some((i1, i2));
+        let count = boxes.len() as f64;
+        let sum = |axis: fn(&Vec3) -> i64| boxes.iter().map(|v| axis(v) as f64).sum::<f64>();
+        let centroid = (
+            sum(|v| v.x) / count,
+            sum(|v| v.y) / count,
+            sum(|v| v.z) / count,
+        );
+
+        let min = boxes.iter().fold(boxes[0], |acc, v| Vec3 {
+            x: acc.x.min(v.x),
+            y: acc.y.min(v.y),
+            z: acc.z.min(v.z),
+        });
+        let max = boxes.iter().fold(boxes[0], |acc, v| Vec3 {
+            x: acc.x.max(v.x),
+            y: acc.y.max(v.y),
+            z: acc.z.max(v.z),
+        });
+
+        Self {
+            members,
+            boxes,
+            centroid,
+            min,
+            max,
+            connections: Vec::new(),
+            wire_length: 0.0,
         }
+    }
 
-        last_pair.map(|(i1, i2)| (self.junction_boxes[i1], self.junction_boxes[i2]))
+    fn size(&self) -> usize {
+        self.members.len()
     }
 }
 
-fn parse_input(input: &str) -> Playground {
+fn parse_input(input: &str, metric: Metric) -> Playground {
     let junction_boxes = input
         .trim()
         .lines()
@@ -144,7 +308,7 @@ fn parse_input(input: &str) -> Playground {
             }
         })
         .collect();
-    Playground::new(junction_boxes)
+    Playground::new(junction_boxes, metric)
 }
 
 #[cfg(test)]
@@ -177,8 +341,9 @@ mod tests {
 
     #[test]
     fn test_distances() {
-        let playground = parse_input(TEST_INPUT);
-        let first_distance = playground.distances[0];
+        let playground = parse_input(TEST_INPUT, Metric::Euclidean);
+        let first_distance = playground.distances().next().unwrap();
+        assert_eq!(first_distance.0, 100427);
         let (i1, i2) = first_distance.1;
         let v1 = playground.junction_boxes[i1];
         let v2 = playground.junction_boxes[i2];
@@ -201,20 +366,92 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_distances_with_metric() {
+        for metric in [Metric::Manhattan, Metric::Chebyshev] {
+            let playground = parse_input(TEST_INPUT, metric);
+            let boxes = &playground.junction_boxes;
+            let closest = (0..boxes.len())
+                .flat_map(|i| (i + 1..boxes.len()).map(move |j| (i, j)))
+                .map(|(i, j)| metric.distance(&boxes[i].as_array(), &boxes[j].as_array()))
+                .min();
+
+            assert_eq!(playground.distances().next().map(|d| d.0), closest);
+            assert!(playground.minimum_spanning_tree().is_spanning());
+        }
+    }
+
     #[test]
     fn test_part1() {
-        let mut playground = parse_input(TEST_INPUT);
+        let mut playground = parse_input(TEST_INPUT, Metric::Euclidean);
         playground.make_connections(10);
-        let sets = playground.disjoint_set.get_sets();
-        assert_eq!(sets.len(), 11);
-        let result: usize = sets.into_iter().take(3).map(|set| set.len()).product();
+        let sizes = playground.disjoint_set.set_sizes();
+        assert_eq!(sizes.len(), 11);
+        let result: usize = sizes.into_iter().take(3).product();
         assert_eq!(result, 40);
     }
 
+    #[test]
+    fn test_circuits() {
+        let mut playground = parse_input(TEST_INPUT, Metric::Euclidean);
+        playground.make_connections(10);
+        let circuits = playground.circuits();
+
+        assert_eq!(circuits.len(), 11);
+        assert_eq!(circuits.iter().map(Circuit::size).sum::<usize>(), 20);
+        assert_eq!(circuits[0].size(), 5);
+        assert_eq!(circuits[0].connections.len(), 4);
+
+        let wire_length = circuits.iter().map(|c| c.wire_length).sum::<f64>();
+        let expected = playground.wire_length(&playground.connections);
+        assert!((wire_length - expected).abs() < 1e-9);
+
+        for circuit in &circuits {
+            for v in &circuit.boxes {
+                assert!(circuit.min.x <= v.x && v.x <= circuit.max.x);
+                assert!(circuit.min.y <= v.y && v.y <= circuit.max.y);
+                assert!(circuit.min.z <= v.z && v.z <= circuit.max.z);
+            }
+        }
+
+        let single = circuits.last().unwrap();
+        let v = single.boxes[0];
+        assert_eq!(single.centroid, (v.x as f64, v.y as f64, v.z as f64));
+        assert_eq!(single.wire_length, 0.0);
+    }
+
+    #[test]
+    fn test_export() {
+        let mut playground = parse_input(TEST_INPUT, Metric::Euclidean);
+        playground.make_connections(10);
+
+        let dot = playground.export_dot();
+        assert!(dot.starts_with("graph circuits {"));
+        assert_eq!(dot.matches("subgraph cluster_").count(), 11);
+        assert_eq!(dot.matches(" -- ").count(), playground.connections.len());
+        assert!(dot.contains("0 [label=\"162,817,812\"];"));
+
+        let json = playground.export_json();
+        assert!(json.starts_with("{\"circuits\":[{\"size\":5,"));
+        assert_eq!(json.matches("\"size\":").count(), 11);
+    }
+
+    #[test]
+    fn test_minimum_spanning_tree() {
+        let playground = parse_input(TEST_INPUT, Metric::Euclidean);
+        let tree = playground.minimum_spanning_tree();
+        assert!(tree.is_spanning());
+        assert_eq!(tree.edges().len(), 19);
+
+        let first = tree.edges()[0];
+        assert_eq!((first.a, first.b), playground.distances().next().unwrap().1);
+    }
+
     #[test]
     fn test_part2() {
-        let mut playground = parse_input(TEST_INPUT);
-        let last_pair = playground.connect_until_single_set();
+        let playground = parse_input(TEST_INPUT, Metric::Euclidean);
+        let tree = playground.minimum_spanning_tree();
+        let last_pair = playground.last_connection(&tree);
         assert_eq!(
             last_pair,
             Some((
@@ -6,11 +6,11 @@ macro_rules! impl_days {
             )+
         }
 
-        fn run_day(day: &str, input: String) -> eyre::Result<()> {
+        fn run_day(day: &str, input: String, args: &[String]) -> eyre::Result<()> {
             ::paste::paste! {
                 match day {
                     $(
-                        $day => [<day $day>]::run(input),
+                        $day => [<day $day>]::run(input, args),
                     )+
                     _ => eyre::bail!("Day {} not implemented", day),
                 }
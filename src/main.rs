@@ -13,6 +13,9 @@ pub(crate) struct AdventCli {
     number: u8,
     /// The suffix of the input file, e.g. "test" or "part2", excluding the underscore
     input_suffix: Option<String>,
+    /// Options for the day itself, passed after a `--`
+    #[arg(last = true)]
+    day_args: Vec<String>,
 }
 
 #[derive(Subcommand)]
@@ -31,5 +34,12 @@ fn main() -> eyre::Result<()> {
     let input_formatted = format!("inputs/{day_formatted}{input_suffix}.txt");
     let input = std::fs::read_to_string(input_formatted)?;
 
-    run_day(&day_formatted, input)
+    run_day(&day_formatted, input, &cli.day_args)
+}
+
+/// Parse the options passed after `--` into a day's own option struct
+///
+/// Like [`Parser::parse`], this prints usage and exits when the options are invalid.
+pub(crate) fn parse_day_options<T: Parser>(day: &str, args: &[String]) -> T {
+    T::parse_from(std::iter::once(format!("advent {day} --")).chain(args.iter().cloned()))
 }
@@ -0,0 +1,152 @@
+/// A union-find (disjoint set) structure over the items `0..len`
+///
+/// Uses union by size and iterative path compression, so `find` never
+/// recurses and chains stay shallow.
+#[derive(Debug, Clone)]
+pub struct DisjointSet {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    components: usize,
+}
+
+impl DisjointSet {
+    /// Create a structure where every item is in its own set
+    pub fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+            components: len,
+        }
+    }
+
+    /// The total number of items
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    /// The number of disjoint sets
+    pub fn component_count(&self) -> usize {
+        self.components
+    }
+
+    /// Find the representative of the set containing `item`
+    pub fn find(&mut self, item: usize) -> usize {
+        let mut root = item;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+
+        // compress the path in a second pass
+        let mut current = item;
+        while self.parent[current] != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+
+        root
+    }
+
+    /// Merge the sets containing `item1` and `item2`, returning whether they were separate
+    pub fn unite(&mut self, item1: usize, item2: usize) -> bool {
+        let mut root1 = self.find(item1);
+        let mut root2 = self.find(item2);
+        if root1 == root2 {
+            return false;
+        }
+
+        if self.size[root1] < self.size[root2] {
+            std::mem::swap(&mut root1, &mut root2);
+        }
+        self.parent[root2] = root1;
+        self.size[root1] += self.size[root2];
+        self.components -= 1;
+        true
+    }
+
+    /// Check whether two items are in the same set
+    pub fn same_set(&mut self, item1: usize, item2: usize) -> bool {
+        self.find(item1) == self.find(item2)
+    }
+
+    /// The size of the set containing `item`
+    pub fn set_size(&mut self, item: usize) -> usize {
+        let root = self.find(item);
+        self.size[root]
+    }
+
+    /// The size of every set, largest first
+    pub fn set_sizes(&self) -> Vec<usize> {
+        let mut sizes = (0..self.len())
+            .filter(|&i| self.parent[i] == i)
+            .map(|i| self.size[i])
+            .collect::<Vec<_>>();
+        sizes.sort_unstable_by(|a, b| b.cmp(a));
+        sizes
+    }
+
+    /// The members of every set, largest set first
+    pub fn sets(&mut self) -> Vec<Vec<usize>> {
+        let mut slots = vec![usize::MAX; self.len()];
+        let mut sets: Vec<Vec<usize>> = Vec::with_capacity(self.components);
+        for item in 0..self.len() {
+            let root = self.find(item);
+            if slots[root] == usize::MAX {
+                slots[root] = sets.len();
+                sets.push(Vec::with_capacity(self.size[root]));
+            }
+            sets[slots[root]].push(item);
+        }
+        sets.sort_by_key(|set| std::cmp::Reverse(set.len()));
+        sets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unite() {
+        let mut set = DisjointSet::new(5);
+        assert_eq!(set.component_count(), 5);
+
+        assert!(set.unite(0, 1));
+        assert!(set.unite(1, 2));
+        assert!(!set.unite(0, 2));
+        assert_eq!(set.component_count(), 3);
+
+        assert!(set.same_set(0, 2));
+        assert!(!set.same_set(0, 3));
+        assert_eq!(set.set_size(2), 3);
+        assert_eq!(set.set_size(4), 1);
+    }
+
+    #[test]
+    fn test_sets() {
+        let mut set = DisjointSet::new(6);
+        set.unite(4, 5);
+        set.unite(0, 2);
+        set.unite(2, 4);
+
+        assert_eq!(set.set_sizes(), vec![4, 1, 1]);
+        assert_eq!(set.sets(), vec![vec![0, 2, 4, 5], vec![1], vec![3]]);
+    }
+
+    #[test]
+    fn test_long_chain() {
+        let len = 1_000_000;
+        let mut set = DisjointSet::new(len);
+        for i in 1..len {
+            set.unite(i - 1, i);
+        }
+
+        assert_eq!(set.component_count(), 1);
+        assert_eq!(set.set_size(0), len);
+        assert!(set.same_set(0, len - 1));
+    }
+}
@@ -376,13 +376,20 @@ impl<T> Grid<T> {
     }
 
     pub fn print(&self, print_cell: impl Fn(Option<&T>) -> &str) {
+        print!("{}", self.render(print_cell));
+    }
+
+    /// Render the grid to a string, one line per row
+    pub fn render(&self, render_cell: impl Fn(Option<&T>) -> &str) -> String {
+        let mut output = String::with_capacity((self.width + 1) * self.height);
         for row in 0..self.height {
             for col in 0..self.width {
                 let cell = self.get((row, col));
-                print!("{}", print_cell(cell));
+                output.push_str(render_cell(cell));
             }
-            println!();
+            output.push('\n');
         }
+        output
     }
 }
 
@@ -685,6 +692,20 @@ mod tests {
         assert_eq!(grid.get((1, 1)), Some(&40));
     }
 
+    #[test]
+    fn test_grid_render() {
+        let mut grid: Grid<char> = Grid::new(3, 2);
+        grid.set((0, 1), 'A');
+        grid.set((1, 2), 'B');
+
+        let rendered = grid.render(|cell| match cell {
+            Some('A') => "a",
+            Some(_) => "b",
+            None => ".",
+        });
+        assert_eq!(rendered, ".a.\n..b\n");
+    }
+
     #[test]
     fn test_grid_into_iter() {
         let data = vec![vec!['X', 'Y']];
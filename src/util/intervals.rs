@@ -0,0 +1,574 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// An inclusive range of values `start..=end`, where `start <= end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Interval<T> {
+    start: T,
+    end: T,
+}
+
+impl<T: Ord + Copy + fmt::Display> Interval<T> {
+    /// Create an interval, failing if `start` comes after `end`
+    pub fn new(start: T, end: T) -> eyre::Result<Self> {
+        if start > end {
+            eyre::bail!("Interval {start}-{end} starts after it ends");
+        }
+        Ok(Self { start, end })
+    }
+
+    pub fn start(&self) -> T {
+        self.start
+    }
+
+    pub fn end(&self) -> T {
+        self.end
+    }
+
+    /// Check whether `value` lies within the interval
+    pub fn contains(&self, value: T) -> bool {
+        self.start <= value && value <= self.end
+    }
+
+    /// Check whether the two intervals share at least one value
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// The values shared by both intervals, if there are any
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        self.overlaps(other).then(|| Self {
+            start: self.start.max(other.start),
+            end: self.end.min(other.end),
+        })
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Interval<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl<T> FromStr for Interval<T>
+where
+    T: FromStr + Ord + Copy + fmt::Display,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    type Err = eyre::Error;
+
+    /// Parse an interval written as `start-end`, ignoring surrounding whitespace
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| eyre::eyre!("Invalid interval {s:?}, expected start-end"))?;
+        Self::new(start.trim().parse()?, end.trim().parse()?)
+    }
+}
+
+/// Two input intervals found to share values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlap<T> {
+    pub first: Interval<T>,
+    pub second: Interval<T>,
+    pub shared: Interval<T>,
+}
+
+/// Find the intervals that share values with an earlier one
+///
+/// Each overlap is reported against the interval reaching furthest among those
+/// before it, so every input interval appears as `second` at most once. This
+/// only reports; collect the intervals into an [`IntervalSet`] to merge them.
+pub fn overlaps<T: Ord + Copy + fmt::Display>(intervals: &[Interval<T>]) -> Vec<Overlap<T>> {
+    let mut sorted = intervals.to_vec();
+    sorted.sort_unstable();
+
+    let mut overlaps = Vec::new();
+    let mut furthest: Option<Interval<T>> = None;
+    for interval in sorted {
+        match furthest {
+            Some(reaching) if interval.start <= reaching.end => {
+                overlaps.push(Overlap {
+                    first: reaching,
+                    second: interval,
+                    shared: reaching.intersection(&interval).unwrap(),
+                });
+                if interval.end > reaching.end {
+                    furthest = Some(interval);
+                }
+            }
+            _ => furthest = Some(interval),
+        }
+    }
+    overlaps
+}
+
+/// Integer-like values with a next and previous value, so intervals can be coalesced
+pub trait Discrete: Ord + Copy + fmt::Display {
+    fn successor(self) -> Option<Self>;
+    fn predecessor(self) -> Option<Self>;
+    /// How many values lie in `start..=end`, saturating at `u128::MAX`
+    fn count_between(start: Self, end: Self) -> u128;
+}
+
+macro_rules! impl_discrete {
+    ($($t:ty => $unsigned:ty),* $(,)?) => {
+        $(
+            impl Discrete for $t {
+                fn successor(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+
+                fn predecessor(self) -> Option<Self> {
+                    self.checked_sub(1)
+                }
+
+                fn count_between(start: Self, end: Self) -> u128 {
+                    // the difference always fits in the unsigned type of the same width
+                    (end.wrapping_sub(start) as $unsigned as u128).saturating_add(1)
+                }
+            }
+        )*
+    };
+}
+
+impl_discrete! {
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, u128 => u128, usize => usize,
+    i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128, isize => usize,
+}
+
+impl<T: Discrete> Interval<T> {
+    /// How many values the interval holds, saturating at `u128::MAX`
+    pub fn width(&self) -> u128 {
+        T::count_between(self.start, self.end)
+    }
+}
+
+/// A set of values stored as sorted, disjoint intervals
+///
+/// Intervals that overlap or touch are coalesced, so `{1-3, 4-6}` is always
+/// stored as `{1-6}` and every gap between stored intervals is non-empty.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IntervalSet<T> {
+    intervals: Vec<Interval<T>>,
+}
+
+impl<T> Default for IntervalSet<T> {
+    fn default() -> Self {
+        Self {
+            intervals: Vec::new(),
+        }
+    }
+}
+
+impl<T: Discrete> IntervalSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a reference to the stored intervals, in ascending order
+    pub fn intervals(&self) -> &[Interval<T>] {
+        &self.intervals
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// The number of values in the set, saturating at `u128::MAX`
+    pub fn len(&self) -> u128 {
+        self.intervals
+            .iter()
+            .map(Interval::width)
+            .fold(0, u128::saturating_add)
+    }
+
+    /// Check whether `value` is in the set
+    pub fn contains(&self, value: T) -> bool {
+        let index = self.intervals.partition_point(|i| i.end < value);
+        self.intervals.get(index).is_some_and(|i| i.contains(value))
+    }
+
+    /// Check whether every value of `interval` is in the set
+    pub fn contains_interval(&self, interval: &Interval<T>) -> bool {
+        let index = self.intervals.partition_point(|i| i.end < interval.start);
+        self.intervals
+            .get(index)
+            .is_some_and(|i| i.start <= interval.start && interval.end <= i.end)
+    }
+
+    /// Add every value of `interval` to the set
+    pub fn insert(&mut self, interval: Interval<T>) {
+        // everything from `lo` to `hi` overlaps or touches the new interval
+        let lo = self
+            .intervals
+            .partition_point(|i| i.end.successor().is_some_and(|next| next < interval.start));
+        let hi = self
+            .intervals
+            .partition_point(|i| interval.end.successor().is_none_or(|next| i.start <= next));
+
+        let mut merged = interval;
+        if lo < hi {
+            merged.start = merged.start.min(self.intervals[lo].start);
+            merged.end = merged.end.max(self.intervals[hi - 1].end);
+        }
+        self.intervals.splice(lo..hi, [merged]);
+    }
+
+    /// Take every value of `interval` out of the set
+    pub fn remove(&mut self, interval: &Interval<T>) {
+        let lo = self.intervals.partition_point(|i| i.end < interval.start);
+        let hi = self.intervals.partition_point(|i| i.start <= interval.end);
+        if lo >= hi {
+            return;
+        }
+
+        // the intervals at either end may stick out past the removed one
+        let first = self.intervals[lo];
+        let last = self.intervals[hi - 1];
+        let left = interval
+            .start
+            .predecessor()
+            .filter(|end| first.start <= *end)
+            .map(|end| Interval {
+                start: first.start,
+                end,
+            });
+        let right = interval
+            .end
+            .successor()
+            .filter(|start| *start <= last.end)
+            .map(|start| Interval {
+                start,
+                end: last.end,
+            });
+        self.intervals.splice(lo..hi, left.into_iter().chain(right));
+    }
+
+    /// Every value in either set
+    pub fn union(&self, other: &Self) -> Self {
+        let mut all = self
+            .intervals
+            .iter()
+            .chain(&other.intervals)
+            .copied()
+            .collect::<Vec<_>>();
+        all.sort_unstable();
+        Self::coalesce(all)
+    }
+
+    /// Every value in both sets
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut intervals = Vec::new();
+        let (mut a, mut b) = (0, 0);
+        while let (Some(x), Some(y)) = (self.intervals.get(a), other.intervals.get(b)) {
+            intervals.extend(x.intersection(y));
+            // whichever ends first can't overlap anything further on
+            if x.end < y.end {
+                a += 1;
+            } else {
+                b += 1;
+            }
+        }
+        Self { intervals }
+    }
+
+    /// Every value in this set but not in `other`
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut difference = self.clone();
+        for interval in &other.intervals {
+            difference.remove(interval);
+        }
+        difference
+    }
+
+    /// Every value within `bounds` that isn't in the set
+    pub fn complement(&self, bounds: Interval<T>) -> Self {
+        let mut complement = Self {
+            intervals: vec![bounds],
+        };
+        for interval in &self.intervals {
+            complement.remove(interval);
+        }
+        complement
+    }
+
+    /// Iterate over the holes between consecutive intervals
+    pub fn gaps(&self) -> impl Iterator<Item = Interval<T>> + '_ {
+        self.intervals.windows(2).map(|pair| Interval {
+            // stored intervals never touch, so both ends exist
+            start: pair[0].end.successor().unwrap(),
+            end: pair[1].start.predecessor().unwrap(),
+        })
+    }
+
+    /// Build a set from intervals sorted by their start
+    fn coalesce(sorted: Vec<Interval<T>>) -> Self {
+        let mut intervals: Vec<Interval<T>> = Vec::with_capacity(sorted.len());
+        for interval in sorted {
+            match intervals.last_mut() {
+                Some(last)
+                    if last
+                        .end
+                        .successor()
+                        .is_none_or(|next| interval.start <= next) =>
+                {
+                    last.end = last.end.max(interval.end);
+                }
+                _ => intervals.push(interval),
+            }
+        }
+        Self { intervals }
+    }
+}
+
+impl<T: Discrete> FromIterator<Interval<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        let mut sorted = iter.into_iter().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        Self::coalesce(sorted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::util::lcg::Lcg;
+
+    fn interval<T: Discrete>(start: T, end: T) -> Interval<T> {
+        Interval::new(start, end).unwrap()
+    }
+
+    fn set_of<T: Discrete>(intervals: &[(T, T)]) -> IntervalSet<T> {
+        intervals
+            .iter()
+            .map(|&(start, end)| interval(start, end))
+            .collect()
+    }
+
+    /// Check every value in `domain` against a plain set of the same values
+    fn assert_matches<T: Discrete + fmt::Debug>(
+        set: &IntervalSet<T>,
+        expected: &BTreeSet<T>,
+        domain: impl Iterator<Item = T>,
+    ) {
+        for value in domain {
+            assert_eq!(
+                set.contains(value),
+                expected.contains(&value),
+                "{value} in {set:?}"
+            );
+        }
+        assert_eq!(set.len(), expected.len() as u128);
+
+        // stored intervals are sorted and never touch
+        for pair in set.intervals().windows(2) {
+            assert!(
+                pair[0].end().successor().unwrap() < pair[1].start(),
+                "{set:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_interval() {
+        let interval = Interval::new(3, 7).unwrap();
+        assert!(interval.contains(3));
+        assert!(interval.contains(7));
+        assert!(!interval.contains(8));
+
+        let other = Interval::new(7, 9).unwrap();
+        assert!(interval.overlaps(&other));
+        assert_eq!(interval.intersection(&other), Interval::new(7, 7).ok());
+        assert_eq!(interval.intersection(&Interval::new(8, 9).unwrap()), None);
+
+        assert!(Interval::new(5, 4).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "11-22\n".parse::<Interval<u64>>().unwrap(),
+            Interval::new(11, 22).unwrap()
+        );
+        assert_eq!(
+            " 5 - 5 ".parse::<Interval<u64>>().unwrap(),
+            Interval::new(5, 5).unwrap()
+        );
+        assert!("22-11".parse::<Interval<u64>>().is_err());
+        assert!("22".parse::<Interval<u64>>().is_err());
+        assert!("a-b".parse::<Interval<u64>>().is_err());
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let intervals = [(10, 14), (1, 3), (12, 18), (4, 5), (2, 3), (13, 20)]
+            .map(|(start, end)| Interval::new(start, end).unwrap());
+
+        let overlaps = overlaps(&intervals)
+            .iter()
+            .map(|o| {
+                (
+                    o.first.to_string(),
+                    o.second.to_string(),
+                    o.shared.to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            overlaps,
+            [
+                ("1-3".into(), "2-3".into(), "2-3".into()),
+                ("10-14".into(), "12-18".into(), "12-14".into()),
+                ("12-18".into(), "13-20".into(), "13-18".into()),
+            ]
+        );
+
+        // touching intervals don't overlap, but still merge into one set
+        let set = intervals.into_iter().collect::<IntervalSet<_>>();
+        assert_eq!(set.intervals(), [interval(1, 5), interval(10, 20)]);
+    }
+
+    #[test]
+    fn test_width() {
+        assert_eq!(interval(3u8, 7).width(), 5);
+        assert_eq!(interval(i8::MIN, i8::MAX).width(), 256);
+        assert_eq!(interval(-5i64, 5).width(), 11);
+        assert_eq!(interval(0u128, u128::MAX).width(), u128::MAX);
+    }
+
+    #[test]
+    fn test_interval_set() {
+        let mut set = set_of(&[(10, 14), (3, 5), (16, 20), (12, 18)]);
+        assert_eq!(set.intervals(), [interval(3, 5), interval(10, 20)]);
+        assert_eq!(set.len(), 14);
+
+        // touching intervals are coalesced
+        set.insert(interval(6, 8));
+        assert_eq!(set.intervals(), [interval(3, 8), interval(10, 20)]);
+        assert_eq!(set.gaps().collect::<Vec<_>>(), [interval(9, 9)]);
+
+        set.remove(&interval(12, 13));
+        assert_eq!(
+            set.intervals(),
+            [interval(3, 8), interval(10, 11), interval(14, 20)]
+        );
+        assert!(set.contains(11));
+        assert!(!set.contains(12));
+        assert!(set.contains_interval(&interval(14, 20)));
+        assert!(!set.contains_interval(&interval(8, 10)));
+
+        assert_eq!(
+            set.complement(interval(0, 25)).intervals(),
+            [
+                interval(0, 2),
+                interval(9, 9),
+                interval(12, 13),
+                interval(21, 25)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interval_set_extremes() {
+        let mut set = set_of(&[(0u8, 10), (250, 255)]);
+        set.insert(interval(11, 249));
+        assert_eq!(set.intervals(), [interval(0, 255)]);
+        assert_eq!(set.len(), 256);
+
+        set.remove(&interval(0, 0));
+        set.remove(&interval(255, 255));
+        assert_eq!(set.intervals(), [interval(1, 254)]);
+        assert_eq!(
+            set.complement(interval(0, 255)).intervals(),
+            [interval(0, 0), interval(255, 255)]
+        );
+
+        let everything = set_of(&[(0u128, u128::MAX)]);
+        assert_eq!(everything.len(), u128::MAX);
+        assert!(everything.complement(interval(5, 10)).is_empty());
+    }
+
+    /// Apply random operations to an interval set and a `BTreeSet` side by side
+    fn check_against_btreeset<T: Discrete + fmt::Debug>(
+        seed: u64,
+        random_value: impl Fn(&mut Lcg) -> T,
+        domain: impl Fn() -> Vec<T>,
+    ) {
+        let mut rng = Lcg::new(seed);
+        let random_interval = |rng: &mut Lcg| {
+            let (a, b) = (random_value(rng), random_value(rng));
+            interval(a.min(b), a.max(b))
+        };
+        let values = |i: Interval<T>| domain().into_iter().filter(move |v| i.contains(*v));
+
+        for _ in 0..50 {
+            let mut set = IntervalSet::new();
+            let mut expected = BTreeSet::new();
+            for _ in 0..rng.next(12) {
+                let interval = random_interval(&mut rng);
+                if rng.next(3) == 0 {
+                    set.remove(&interval);
+                    for v in values(interval) {
+                        expected.remove(&v);
+                    }
+                } else {
+                    set.insert(interval);
+                    expected.extend(values(interval));
+                }
+                assert_matches(&set, &expected, domain().into_iter());
+            }
+
+            let other = (0..rng.next(6))
+                .map(|_| random_interval(&mut rng))
+                .collect::<IntervalSet<_>>();
+            let other_expected = other
+                .intervals()
+                .iter()
+                .copied()
+                .flat_map(values)
+                .collect::<BTreeSet<_>>();
+
+            let union = expected.union(&other_expected).copied().collect();
+            assert_matches(&set.union(&other), &union, domain().into_iter());
+            let intersection = expected.intersection(&other_expected).copied().collect();
+            assert_matches(
+                &set.intersection(&other),
+                &intersection,
+                domain().into_iter(),
+            );
+            let difference = expected.difference(&other_expected).copied().collect();
+            assert_matches(&set.difference(&other), &difference, domain().into_iter());
+
+            let bounds = random_interval(&mut rng);
+            let complement = values(bounds).filter(|v| !expected.contains(v)).collect();
+            assert_matches(&set.complement(bounds), &complement, domain().into_iter());
+
+            let gaps = set.gaps().flat_map(values).collect::<BTreeSet<_>>();
+            let (first, last) = (expected.first(), expected.last());
+            let holes = domain()
+                .into_iter()
+                .filter(|v| Some(v) > first && Some(v) < last && !expected.contains(v))
+                .collect();
+            assert_eq!(gaps, holes);
+        }
+    }
+
+    #[test]
+    fn test_interval_set_matches_btreeset() {
+        // every u8, so the ends of the type are hit often
+        check_against_btreeset(
+            1,
+            |rng| match rng.next(4) {
+                0 => rng.next(8) as u8,
+                1 => 255 - rng.next(8) as u8,
+                _ => rng.next(256) as u8,
+            },
+            || (0..=u8::MAX).collect(),
+        );
+        check_against_btreeset(2, |rng| rng.next(81) as i16 - 40, || (-45..=45).collect());
+    }
+}
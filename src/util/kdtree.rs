@@ -0,0 +1,329 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// How many neighbors to fetch for each point before the first refill
+const INITIAL_BATCH: usize = 4;
+
+/// A way of measuring the distance between two integer points
+///
+/// Every distance is an exact `i128`. Euclidean distances are kept squared,
+/// which orders the same as the true distance without needing a square root.
+/// A squared distance too large for `i128` saturates at `i128::MAX`, which
+/// only happens when coordinates are more than about `2^62` apart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Metric {
+    #[default]
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+}
+
+impl Metric {
+    /// The exact distance between two points (squared for Euclidean)
+    pub fn distance<const D: usize>(&self, a: &[i64; D], b: &[i64; D]) -> i128 {
+        let diffs = a
+            .iter()
+            .zip(b)
+            .map(|(&a, &b)| (a as i128 - b as i128).abs());
+        match self {
+            Metric::Euclidean => diffs
+                .map(square)
+                .try_fold(0i128, |sum, d| sum.checked_add(d?))
+                .unwrap_or(i128::MAX),
+            Metric::Manhattan => diffs.sum(),
+            Metric::Chebyshev => diffs.max().unwrap_or(0),
+        }
+    }
+
+    /// The smallest distance possible between two points `diff` apart on one axis
+    fn axis_bound(&self, diff: i128) -> i128 {
+        match self {
+            Metric::Euclidean => square(diff).unwrap_or(i128::MAX),
+            Metric::Manhattan | Metric::Chebyshev => diff.abs(),
+        }
+    }
+
+    /// Convert a distance from [`Metric::distance`] into a real length
+    pub fn length(&self, distance: i128) -> f64 {
+        match self {
+            Metric::Euclidean => (distance as f64).sqrt(),
+            Metric::Manhattan | Metric::Chebyshev => distance as f64,
+        }
+    }
+}
+
+/// `diff` squared, or `None` if that overflows `i128`
+fn square(diff: i128) -> Option<i128> {
+    diff.checked_mul(diff)
+}
+
+/// A static k-d tree over integer points, supporting nearest-neighbor queries
+///
+/// Distances are exact integers measured with the tree's [`Metric`], so there
+/// are no floating point ties or NaNs to worry about.
+#[derive(Debug, Clone)]
+pub struct KdTree<const D: usize> {
+    points: Vec<[i64; D]>,
+    metric: Metric,
+    /// Point indices arranged so every subrange is split at its median
+    order: Vec<usize>,
+}
+
+impl<const D: usize> KdTree<D> {
+    pub fn new(points: Vec<[i64; D]>) -> Self {
+        Self::with_metric(points, Metric::Euclidean)
+    }
+
+    pub fn with_metric(points: Vec<[i64; D]>, metric: Metric) -> Self {
+        let mut order = (0..points.len()).collect::<Vec<_>>();
+        build(&points, &mut order, 0);
+        Self {
+            points,
+            metric,
+            order,
+        }
+    }
+
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Get the point stored at `index`
+    pub fn point(&self, index: usize) -> [i64; D] {
+        self.points[index]
+    }
+
+    /// Find the `k` points closest to `target` that pass `filter`
+    ///
+    /// Results are `(distance, index)` pairs sorted by distance, then by index.
+    pub fn nearest(
+        &self,
+        target: &[i64; D],
+        k: usize,
+        filter: impl Fn(usize) -> bool,
+    ) -> Vec<(i128, usize)> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        if k > 0 {
+            self.search(0, self.order.len(), 0, target, k, &filter, &mut heap);
+        }
+        heap.into_sorted_vec()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        &self,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        target: &[i64; D],
+        k: usize,
+        filter: &impl Fn(usize) -> bool,
+        heap: &mut BinaryHeap<(i128, usize)>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+
+        let mid = lo + (hi - lo) / 2;
+        let index = self.order[mid];
+        let point = &self.points[index];
+
+        if filter(index) {
+            let candidate = (self.metric.distance(point, target), index);
+            if heap.len() < k {
+                heap.push(candidate);
+            } else if heap.peek().is_some_and(|worst| candidate < *worst) {
+                heap.pop();
+                heap.push(candidate);
+            }
+        }
+
+        let axis = depth % D;
+        let diff = target[axis] as i128 - point[axis] as i128;
+        let (near, far) = if diff < 0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+
+        self.search(near.0, near.1, depth + 1, target, k, filter, heap);
+
+        // equal distances still need visiting, since a lower index may win the tie
+        let bound = self.metric.axis_bound(diff);
+        if heap.len() < k || heap.peek().is_some_and(|worst| bound <= worst.0) {
+            self.search(far.0, far.1, depth + 1, target, k, filter, heap);
+        }
+    }
+
+    /// Iterate over every pair of points in order of increasing distance
+    ///
+    /// Pairs are produced lazily as `(distance, i, j)` with `i < j`, and
+    /// ties are broken by `i` and then `j`.
+    pub fn closest_pairs(&self) -> ClosestPairs<'_, D> {
+        let mut pairs = ClosestPairs {
+            tree: self,
+            neighbors: Vec::with_capacity(self.len()),
+            batch: vec![INITIAL_BATCH; self.len()],
+            cursor: vec![0; self.len()],
+            heap: BinaryHeap::with_capacity(self.len()),
+        };
+
+        for i in 0..self.len() {
+            let neighbors = self.nearest(&self.points[i], INITIAL_BATCH, |j| j > i);
+            if let Some(&(distance, j)) = neighbors.first() {
+                pairs.heap.push(Reverse((distance, i, j)));
+            }
+            pairs.neighbors.push(neighbors);
+        }
+
+        pairs
+    }
+}
+
+fn build<const D: usize>(points: &[[i64; D]], order: &mut [usize], depth: usize) {
+    if order.len() <= 1 {
+        return;
+    }
+
+    let axis = depth % D;
+    let mid = order.len() / 2;
+    order.select_nth_unstable_by_key(mid, |&i| points[i][axis]);
+
+    let (left, right) = order.split_at_mut(mid);
+    build(points, left, depth + 1);
+    build(points, &mut right[1..], depth + 1);
+}
+
+/// Lazy iterator over point pairs in increasing distance, see [`KdTree::closest_pairs`]
+///
+/// Each point keeps a sorted batch of its nearest higher-indexed neighbors; a
+/// heap merges the heads of those batches, and a batch is refetched at double
+/// the size whenever it runs out.
+pub struct ClosestPairs<'a, const D: usize> {
+    tree: &'a KdTree<D>,
+    neighbors: Vec<Vec<(i128, usize)>>,
+    batch: Vec<usize>,
+    cursor: Vec<usize>,
+    heap: BinaryHeap<Reverse<(i128, usize, usize)>>,
+}
+
+impl<const D: usize> Iterator for ClosestPairs<'_, D> {
+    type Item = (i128, usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((distance, i, j)) = self.heap.pop()?;
+
+        self.cursor[i] += 1;
+        let cursor = self.cursor[i];
+
+        // only a full batch can have more neighbors behind it
+        if cursor == self.neighbors[i].len() && cursor == self.batch[i] {
+            self.batch[i] *= 2;
+            let point = self.tree.points[i];
+            self.neighbors[i] = self.tree.nearest(&point, self.batch[i], |j| j > i);
+        }
+
+        if let Some(&(next_distance, next_j)) = self.neighbors[i].get(cursor) {
+            self.heap.push(Reverse((next_distance, i, next_j)));
+        }
+
+        Some((distance, i, j))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::lcg::Lcg;
+
+    fn random_points(count: usize, seed: u64) -> Vec<[i64; 3]> {
+        let mut rng = Lcg::new(seed);
+        let mut next = move || rng.next(50) as i64;
+        (0..count).map(|_| [next(), next(), next()]).collect()
+    }
+
+    const METRICS: [Metric; 3] = [Metric::Euclidean, Metric::Manhattan, Metric::Chebyshev];
+
+    #[test]
+    fn test_metric_distance() {
+        let a = [1, 2, 3];
+        let b = [4, -2, 3];
+        assert_eq!(Metric::Euclidean.distance(&a, &b), 25);
+        assert_eq!(Metric::Manhattan.distance(&a, &b), 7);
+        assert_eq!(Metric::Chebyshev.distance(&a, &b), 4);
+        assert_eq!(Metric::Euclidean.length(25), 5.0);
+
+        // differences that overflow i64 are still exact
+        let far = [i64::MIN, i64::MIN, i64::MIN];
+        let near = [i64::MAX, i64::MAX, i64::MAX];
+        let span = i64::MAX as i128 - i64::MIN as i128;
+        assert_eq!(Metric::Manhattan.distance(&far, &near), span * 3);
+        assert_eq!(Metric::Chebyshev.distance(&far, &near), span);
+
+        // squared distances are exact while they fit, and saturate beyond that
+        let bound = 1 << 61;
+        let (low, high) = ([-bound; 3], [bound; 3]);
+        let exact = 3 * (2 * bound as i128).pow(2);
+        assert_eq!(Metric::Euclidean.distance(&low, &high), exact);
+        assert_eq!(Metric::Euclidean.distance(&far, &near), i128::MAX);
+        assert_eq!(
+            Metric::Euclidean.distance(&[i64::MIN], &[i64::MAX]),
+            i128::MAX
+        );
+        assert_eq!(Metric::Euclidean.axis_bound(span), i128::MAX);
+    }
+
+    #[test]
+    fn test_nearest() {
+        let points = random_points(200, 7);
+        for metric in METRICS {
+            let tree = KdTree::with_metric(points.clone(), metric);
+
+            for target in points.iter().take(20) {
+                let mut expected = points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| (metric.distance(p, target), i))
+                    .collect::<Vec<_>>();
+                expected.sort();
+                expected.truncate(10);
+
+                assert_eq!(tree.nearest(target, 10, |_| true), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_nearest_filtered() {
+        let tree = KdTree::new(vec![[0, 0], [1, 0], [5, 5], [2, 0]]);
+        assert_eq!(tree.nearest(&[0, 0], 2, |i| i != 0), vec![(1, 1), (4, 3)]);
+        assert_eq!(tree.nearest(&[0, 0], 0, |_| true), vec![]);
+    }
+
+    #[test]
+    fn test_closest_pairs() {
+        // a tiny coordinate range forces plenty of distance ties
+        let points = random_points(120, 42);
+        for metric in METRICS {
+            let tree = KdTree::with_metric(points.clone(), metric);
+
+            let mut expected = Vec::new();
+            for i in 0..points.len() {
+                for j in i + 1..points.len() {
+                    expected.push((metric.distance(&points[i], &points[j]), i, j));
+                }
+            }
+            expected.sort();
+
+            assert_eq!(tree.closest_pairs().collect::<Vec<_>>(), expected);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+/// A tiny LCG, so randomized tests are reproducible without extra dependencies
+pub struct Lcg(u64);
+
+impl Lcg {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// The next pseudo-random value in `0..limit`
+    pub fn next(&mut self, limit: u64) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) % limit
+    }
+}
@@ -1,3 +1,9 @@
+pub mod dsu;
 mod grid;
+pub mod intervals;
+pub mod kdtree;
+#[cfg(test)]
+pub mod lcg;
+pub mod mst;
 
 pub use grid::*;
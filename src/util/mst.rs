@@ -0,0 +1,128 @@
+use std::iter::Sum;
+
+use super::dsu::DisjointSet;
+
+/// A weighted, undirected edge between two items
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edge<W> {
+    pub weight: W,
+    pub a: usize,
+    pub b: usize,
+}
+
+impl<W> Edge<W> {
+    pub fn new(weight: W, a: usize, b: usize) -> Self {
+        Self { weight, a, b }
+    }
+}
+
+/// The result of running Kruskal's algorithm over a set of edges
+#[derive(Debug, Clone)]
+pub struct SpanningTree<W> {
+    node_count: usize,
+    edges: Vec<Edge<W>>,
+}
+
+impl<W> SpanningTree<W> {
+    /// The edges that merged two components, in the order they were added
+    pub fn edges(&self) -> &[Edge<W>] {
+        &self.edges
+    }
+
+    /// The edge that merged the last two components
+    pub fn last_edge(&self) -> Option<&Edge<W>> {
+        self.edges.last()
+    }
+
+    /// Whether the edges connect every node into a single component
+    pub fn is_spanning(&self) -> bool {
+        self.edges.len() + 1 >= self.node_count
+    }
+
+    /// The number of components left once every edge has been considered
+    pub fn component_count(&self) -> usize {
+        self.node_count - self.edges.len()
+    }
+
+    /// The sum of every edge weight in the tree, exactly as the edges were weighted
+    ///
+    /// This is only a total length when the weights are lengths. For weights
+    /// that merely order the same way, such as squared Euclidean distances,
+    /// convert each edge's weight first and sum those instead.
+    pub fn total_weight(&self) -> W
+    where
+        W: Copy + Sum,
+    {
+        self.edges.iter().map(|edge| edge.weight).sum()
+    }
+}
+
+/// Build a minimum spanning forest over `node_count` nodes
+///
+/// `edges` must already be sorted by increasing weight. Edges are only pulled
+/// until every node is connected, so a lazy source is never fully drained.
+pub fn kruskal<W>(node_count: usize, edges: impl IntoIterator<Item = Edge<W>>) -> SpanningTree<W> {
+    let mut disjoint_set = DisjointSet::new(node_count);
+    let mut tree = Vec::with_capacity(node_count.saturating_sub(1));
+
+    let mut edges = edges.into_iter();
+    while disjoint_set.component_count() > 1 {
+        let Some(edge) = edges.next() else {
+            break;
+        };
+
+        if disjoint_set.unite(edge.a, edge.b) {
+            tree.push(edge);
+        }
+    }
+
+    SpanningTree {
+        node_count,
+        edges: tree,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kruskal() {
+        let edges = vec![
+            Edge::new(1, 0, 1),
+            Edge::new(2, 1, 2),
+            Edge::new(3, 0, 2),
+            Edge::new(4, 2, 3),
+            Edge::new(5, 1, 3),
+        ];
+        let tree = kruskal(4, edges);
+
+        assert!(tree.is_spanning());
+        assert_eq!(tree.edges().len(), 3);
+        assert_eq!(tree.total_weight(), 7);
+        assert_eq!(tree.last_edge(), Some(&Edge::new(4, 2, 3)));
+    }
+
+    #[test]
+    fn test_kruskal_stops_early() {
+        let mut pulled = 0;
+        let edges = (1..).map(|i| {
+            pulled += 1;
+            Edge::new(i, 0, i)
+        });
+        let tree = kruskal(5, edges);
+
+        assert!(tree.is_spanning());
+        assert_eq!(pulled, 4);
+    }
+
+    #[test]
+    fn test_kruskal_forest() {
+        let edges = vec![Edge::new(1.5, 0, 1), Edge::new(2.5, 2, 3)];
+        let tree = kruskal(5, edges);
+
+        assert!(!tree.is_spanning());
+        assert_eq!(tree.component_count(), 3);
+        assert_eq!(tree.total_weight(), 4.0);
+    }
+}